@@ -0,0 +1,96 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program::invoke,
+};
+use mpl_bubblegum::{types::LeafSchema, utils::get_asset_id};
+
+use crate::ErrorCode;
+
+/// SPL account compression program, owner of the ticket Merkle trees.
+pub const SPL_ACCOUNT_COMPRESSION_ID: Pubkey =
+    pubkey!("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+/// SPL noop program Bubblegum logs leaf changes through.
+pub const SPL_NOOP_ID: Pubkey = pubkey!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+
+/// Anchor discriminator of the account compression `verify_leaf` instruction.
+const VERIFY_LEAF_DISCRIMINATOR: [u8; 8] = [124, 220, 22, 223, 104, 10, 250, 224];
+
+/// Proof that a compressed ticket leaf is in the lottery's Merkle tree. The proof nodes
+/// themselves are the first `proof_len` remaining accounts; any accounts after them are
+/// passed on to the payment transfer.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct TicketLeafProof {
+    pub root: [u8; 32],
+    pub data_hash: [u8; 32],
+    pub creator_hash: [u8; 32],
+    pub proof_len: u8,
+}
+
+impl TicketLeafProof {
+    /// Splits the remaining accounts into the proof nodes and the transfer hook accounts.
+    pub fn split_remaining_accounts<'a, 'info>(
+        &self,
+        remaining_accounts: &'a [AccountInfo<'info>],
+    ) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+        require!(
+            remaining_accounts.len() >= self.proof_len as usize,
+            ErrorCode::IncorrectTicket
+        );
+        Ok(remaining_accounts.split_at(self.proof_len as usize))
+    }
+}
+
+/// Bubblegum leaf index of a ticket. Every ticket is minted as its own leaf in a tree
+/// only the lottery can mint into, so the leaf nonce is the ticket number.
+pub fn leaf_index(ticket: u64) -> Result<u32> {
+    u32::try_from(ticket).map_err(|_| ErrorCode::ArithmeticOverflow.into())
+}
+
+/// Checks through the account compression program that `owner` holds the compressed
+/// ticket `ticket` of `merkle_tree`.
+pub fn verify_ticket_leaf<'info>(
+    compression_program: &AccountInfo<'info>,
+    merkle_tree: &AccountInfo<'info>,
+    proof_accounts: &[AccountInfo<'info>],
+    owner: Pubkey,
+    delegate: Pubkey,
+    ticket: u64,
+    proof: &TicketLeafProof,
+) -> Result<()> {
+    let leaf = LeafSchema::V1 {
+        id: get_asset_id(merkle_tree.key, ticket),
+        owner,
+        delegate,
+        nonce: ticket,
+        data_hash: proof.data_hash,
+        creator_hash: proof.creator_hash,
+    }
+    .hash();
+
+    let mut data = VERIFY_LEAF_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&proof.root);
+    data.extend_from_slice(&leaf);
+    data.extend_from_slice(&leaf_index(ticket)?.to_le_bytes());
+
+    let mut accounts = vec![AccountMeta::new_readonly(*merkle_tree.key, false)];
+    accounts.extend(
+        proof_accounts
+            .iter()
+            .map(|node| AccountMeta::new_readonly(*node.key, false)),
+    );
+
+    let mut account_infos = vec![merkle_tree.clone()];
+    account_infos.extend_from_slice(proof_accounts);
+    account_infos.push(compression_program.clone());
+
+    invoke(
+        &Instruction {
+            program_id: SPL_ACCOUNT_COMPRESSION_ID,
+            accounts,
+            data,
+        },
+        &account_infos,
+    )
+    .map_err(|_| ErrorCode::IncorrectTicket.into())
+}
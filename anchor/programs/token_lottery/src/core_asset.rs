@@ -0,0 +1,105 @@
+use std::ops::Range;
+
+use anchor_lang::prelude::*;
+use mpl_core::{
+    accounts::BaseAssetV1,
+    fetch_plugin,
+    types::{Attribute, Attributes, Key, Plugin, PluginAuthorityPair, PluginType, UpdateAuthority},
+};
+
+use crate::ErrorCode;
+
+/// Attribute holding the first ticket number of a Core ticket asset.
+pub const FIRST_TICKET_ATTRIBUTE: &str = "first_ticket";
+/// Attribute holding the number of consecutive tickets of a Core ticket asset.
+pub const QUANTITY_ATTRIBUTE: &str = "quantity";
+
+/// Attributes plugin recording the range of a Core ticket asset. It is managed by the
+/// update authority, the lottery's collection, so the holder can't edit it.
+pub fn ticket_attributes(first_ticket: u64, quantity: u64) -> PluginAuthorityPair {
+    PluginAuthorityPair {
+        plugin: Plugin::Attributes(Attributes {
+            attribute_list: vec![
+                Attribute {
+                    key: FIRST_TICKET_ATTRIBUTE.to_string(),
+                    value: first_ticket.to_string(),
+                },
+                Attribute {
+                    key: QUANTITY_ATTRIBUTE.to_string(),
+                    value: quantity.to_string(),
+                },
+            ],
+        }),
+        authority: None,
+    }
+}
+
+/// Reads the tickets back from the attributes written by `ticket_attributes`.
+pub fn tickets_from_attributes(attributes: &Attributes) -> Result<Range<u64>> {
+    let attribute = |key: &str| -> Result<u64> {
+        attributes
+            .attribute_list
+            .iter()
+            .find(|attribute| attribute.key == key)
+            .and_then(|attribute| attribute.value.parse().ok())
+            .ok_or(ErrorCode::NotVerifiedTicket.into())
+    };
+
+    let first_ticket = attribute(FIRST_TICKET_ATTRIBUTE)?;
+    let end = first_ticket
+        .checked_add(attribute(QUANTITY_ATTRIBUTE)?)
+        .ok_or(ErrorCode::NotVerifiedTicket)?;
+    Ok(first_ticket..end)
+}
+
+/// Checks that `asset` is a live Core asset of `collection` held by `owner`, and returns
+/// the tickets recorded in its attributes.
+pub fn verify_ticket_asset(
+    asset: &AccountInfo,
+    collection: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Range<u64>> {
+    require_keys_eq!(*asset.owner, mpl_core::ID, ErrorCode::NotVerifiedTicket);
+
+    // Burned assets are left as a single `Uninitialized` key byte and fail to deserialize
+    let base = BaseAssetV1::try_from(asset).map_err(|_| ErrorCode::NotVerifiedTicket)?;
+    require!(
+        base.key == Key::AssetV1
+            && base.update_authority == UpdateAuthority::Collection(*collection),
+        ErrorCode::NotVerifiedTicket
+    );
+    require_keys_eq!(base.owner, *owner, ErrorCode::IncorrectTicket);
+
+    let (_, attributes, _) = fetch_plugin::<BaseAssetV1, Attributes>(asset, PluginType::Attributes)
+        .map_err(|_| ErrorCode::NotVerifiedTicket)?;
+    tickets_from_attributes(&attributes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes_of(pair: PluginAuthorityPair) -> Attributes {
+        match pair.plugin {
+            Plugin::Attributes(attributes) => attributes,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn attributes_round_trip_the_tickets() {
+        let tickets = tickets_from_attributes(&attributes_of(ticket_attributes(12, 5))).unwrap();
+
+        assert_eq!(tickets, 12..17);
+    }
+
+    #[test]
+    fn rejects_missing_or_malformed_attributes() {
+        let mut attributes = attributes_of(ticket_attributes(12, 5));
+        attributes.attribute_list[1].value = "five".to_string();
+        assert!(tickets_from_attributes(&attributes).is_err());
+
+        attributes.attribute_list.pop();
+        assert!(tickets_from_attributes(&attributes).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::ops::Range;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{entrypoint::MAX_PERMITTED_DATA_INCREASE, pubkey::PUBKEY_BYTES};
+
+use crate::ErrorCode;
+
+/// Tickets a single ledger purchase can add: an account may only grow by
+/// `MAX_PERMITTED_DATA_INCREASE` bytes per instruction.
+pub const MAX_LEDGER_TICKETS_PER_PURCHASE: u64 =
+    (MAX_PERMITTED_DATA_INCREASE / PUBKEY_BYTES) as u64;
+
+/// Owners of the tickets of a `Ledger` lottery. The header is followed by one pubkey per
+/// ticket, in ticket order; the account is reallocated as tickets are sold.
+#[account(zero_copy)]
+pub struct TicketRegistry {
+    pub lottery_id: u64,
+    /// Number of entries following the header
+    pub len: u64,
+}
+
+impl TicketRegistry {
+    /// Size of the header, discriminator included.
+    pub const HEADER_LEN: usize = 8 + std::mem::size_of::<TicketRegistry>();
+
+    /// Account size holding `entries` tickets.
+    pub fn space(entries: u64) -> usize {
+        Self::HEADER_LEN + entries as usize * PUBKEY_BYTES
+    }
+}
+
+fn entry_range(data: &[u8], ticket: u64) -> Result<Range<usize>> {
+    let start = (ticket as usize)
+        .checked_mul(PUBKEY_BYTES)
+        .and_then(|offset| offset.checked_add(TicketRegistry::HEADER_LEN))
+        .ok_or(ErrorCode::IncorrectTicket)?;
+    let end = start + PUBKEY_BYTES;
+
+    require!(end <= data.len(), ErrorCode::IncorrectTicket);
+    Ok(start..end)
+}
+
+/// Records `owner` as the holder of tickets `first_ticket..first_ticket + quantity`.
+pub fn write_entries(
+    data: &mut [u8],
+    first_ticket: u64,
+    owner: &Pubkey,
+    quantity: u64,
+) -> Result<()> {
+    for ticket in first_ticket..first_ticket + quantity {
+        let range = entry_range(data, ticket)?;
+        data[range].copy_from_slice(owner.as_ref());
+    }
+    Ok(())
+}
+
+/// Holder of `ticket`, `Pubkey::default()` once it has been refunded.
+pub fn read_entry(data: &[u8], ticket: u64) -> Result<Pubkey> {
+    let range = entry_range(data, ticket)?;
+    Ok(Pubkey::try_from(&data[range]).unwrap())
+}
+
+/// Clears the holder of a refunded ticket so it can't be refunded twice.
+pub fn clear_entry(data: &mut [u8], ticket: u64) -> Result<()> {
+    let range = entry_range(data, ticket)?;
+    data[range].fill(0);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_follow_the_header() {
+        let mut data = vec![0u8; TicketRegistry::space(3)];
+        let alice = Pubkey::new_unique();
+        let bob = Pubkey::new_unique();
+
+        write_entries(&mut data, 0, &alice, 2).unwrap();
+        write_entries(&mut data, 2, &bob, 1).unwrap();
+
+        assert!(data[..TicketRegistry::HEADER_LEN]
+            .iter()
+            .all(|byte| *byte == 0));
+        assert_eq!(read_entry(&data, 0).unwrap(), alice);
+        assert_eq!(read_entry(&data, 1).unwrap(), alice);
+        assert_eq!(read_entry(&data, 2).unwrap(), bob);
+    }
+
+    #[test]
+    fn rejects_tickets_past_the_end() {
+        let mut data = vec![0u8; TicketRegistry::space(2)];
+
+        assert!(write_entries(&mut data, 1, &Pubkey::new_unique(), 2).is_err());
+        assert!(read_entry(&data, 2).is_err());
+    }
+
+    #[test]
+    fn cleared_entries_read_as_default() {
+        let mut data = vec![0u8; TicketRegistry::space(1)];
+        write_entries(&mut data, 0, &Pubkey::new_unique(), 1).unwrap();
+
+        clear_entry(&mut data, 0).unwrap();
+
+        assert_eq!(read_entry(&data, 0).unwrap(), Pubkey::default());
+    }
+}
@@ -1,41 +1,126 @@
 use anchor_lang::prelude::*;
-use anchor_lang::{accounts::signer, system_program};
-use anchor_spl::metadata::MetadataAccount;
 use anchor_spl::{
     associated_token::AssociatedToken,
     metadata::{
-        create_master_edition_v3, create_metadata_accounts_v3,
-        mpl_token_metadata::types::{CollectionDetails, Creator, DataV2},
-        set_and_verify_sized_collection_item, sign_metadata, CreateMasterEditionV3,
-        CreateMetadataAccountsV3, Metadata, SetAndVerifySizedCollectionItem, SignMetadata,
+        burn_nft, create_master_edition_v3, create_metadata_accounts_v3,
+        mpl_token_metadata::{
+            self,
+            types::{CollectionDetails, Creator, DataV2},
+        },
+        set_and_verify_sized_collection_item, sign_metadata, update_metadata_accounts_v2, BurnNft,
+        CreateMasterEditionV3, CreateMetadataAccountsV3, Metadata, MetadataAccount,
+        SetAndVerifySizedCollectionItem, SignMetadata, UpdateMetadataAccountsV2,
     },
-    token_interface::{mint_to, Mint, MintTo, TokenAccount, TokenInterface},
+    token_2022::{
+        spl_token_2022::{extension::ExtensionType, instruction::AuthorityType},
+        Token2022,
+    },
+    token_2022_extensions::{
+        spl_token_metadata_interface::state::Field, token_group_initialize,
+        token_member_initialize, token_metadata_initialize, token_metadata_update_field,
+        TokenGroupInitialize, TokenMemberInitialize, TokenMetadataInitialize,
+        TokenMetadataUpdateField,
+    },
+    token_interface::{
+        burn, close_account, mint_to, set_authority, Burn, CloseAccount, Mint, MintTo,
+        SetAuthority, TokenAccount, TokenInterface,
+    },
+};
+use mpl_bubblegum::{
+    instructions::{BurnCpiBuilder, CreateTreeConfigCpiBuilder, MintToCollectionV1CpiBuilder},
+    types::{Collection, MetadataArgs, TokenProgramVersion, TokenStandard},
 };
-use switchboard_on_demand::RandomnessAccountData;
+use mpl_core::instructions::{BurnV1CpiBuilder, CreateCollectionV2CpiBuilder, CreateV2CpiBuilder};
+
+pub mod compressed;
+pub mod core_asset;
+pub mod ledger;
+pub mod payment;
+pub mod randomness;
+pub mod sampling;
+pub mod ticket_metadata;
+pub mod token_extensions;
+
+use compressed::{
+    leaf_index, verify_ticket_leaf, TicketLeafProof, SPL_ACCOUNT_COMPRESSION_ID, SPL_NOOP_ID,
+};
+use core_asset::{ticket_attributes, verify_ticket_asset};
+use ledger::{
+    clear_entry, read_entry, write_entries, TicketRegistry, MAX_LEDGER_TICKETS_PER_PURCHASE,
+};
+use payment::{
+    amount_after_fee, collect_payment, drain_vault, pay_out, validate_payment_mint, TokenPayment,
+};
+use randomness::RandomnessSource;
+use sampling::draw_winners;
+use ticket_metadata::CollectionMetadata;
+use token_extensions::{fund_mint_growth, mint_len_after_update, mint_len_with_metadata};
 
 declare_id!("BdRpZcRTZiZ6K25izHE8Sb497LLr2CCKvY4uFoGxVJwz");
 
 #[constant]
-pub const NAME: &str = "Token Lottery Ticket #";
+pub const MAX_PRIZE_TIERS: usize = 8;
 #[constant]
-pub const SYMBOL: &str = "TLT";
+pub const BASIS_POINTS: u16 = 10_000;
 #[constant]
-pub const URI: &str = "https://raw.githubusercontent.com/solana-developers/developer-bootcamp-2024/refs/heads/main/project-9-token-lottery/metadata.json";
+pub const MAX_CRANK_BOUNTY_BPS: u16 = 100;
 
 #[program]
 pub mod token_lottery {
     use super::*;
 
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize_config(
         ctx: Context<Initialize>,
+        lottery_id: u64,
         start_time: u64,
         end_time: u64,
         ticket_price: u64,
+        prize_tiers: Vec<u16>,
+        min_tickets: u64,
+        ticket_mode: TicketMode,
+        claim_period: u64,
+        close_recipient: Pubkey,
+        crank_bounty_bps: u16,
+        reveal_timeout: u64,
+        randomness_source: RandomnessSource,
     ) -> Result<()> {
+        require!(
+            !prize_tiers.is_empty() && prize_tiers.len() <= MAX_PRIZE_TIERS,
+            ErrorCode::InvalidPrizeTiers
+        );
+        require!(
+            prize_tiers.iter().all(|share| *share > 0)
+                && prize_tiers.iter().map(|share| *share as u32).sum::<u32>()
+                    == BASIS_POINTS as u32,
+            ErrorCode::InvalidPrizeTiers
+        );
+
+        require!(
+            crank_bounty_bps <= MAX_CRANK_BOUNTY_BPS,
+            ErrorCode::InvalidCrankBounty
+        );
+        require!(
+            reveal_timeout > randomness_source.reveal_delay(),
+            ErrorCode::InvalidRevealTimeout
+        );
+        randomness_source.ensure_enabled()?;
+
+        // A mint stored without its vault would leave a lottery no purchase can pay into
+        require!(
+            ctx.accounts.payment_mint.is_some() == ctx.accounts.vault.is_some()
+                && ctx.accounts.vault.is_some() == ctx.accounts.payment_token_program.is_some(),
+            ErrorCode::MissingPaymentAccounts
+        );
+        if let Some(payment_mint) = ctx.accounts.payment_mint.as_ref() {
+            validate_payment_mint(payment_mint)?;
+        }
+
         *ctx.accounts.token_lottery = TokenLottery {
             bump: ctx.bumps.token_lottery,
-            winner: 0,
-            winner_chosen: false,
+            lottery_id,
+            winners: Vec::new(),
+            status: LotteryStatus::Configured,
             start_time,
             end_time,
             lottery_pot_amount: 0,
@@ -43,6 +128,28 @@ pub mod token_lottery {
             ticket_price,
             authority: *ctx.accounts.payer.key,
             randomness_account: Pubkey::default(),
+            payment_mint: ctx
+                .accounts
+                .payment_mint
+                .as_ref()
+                .map(|mint| mint.key())
+                .unwrap_or_default(),
+            prize_tiers,
+            prize_pool: 0,
+            claimed_tiers: 0,
+            min_tickets,
+            tickets_refunded: 0,
+            ticket_mode,
+            merkle_tree: Pubkey::default(),
+            metadata: CollectionMetadata::default(),
+            claim_period,
+            close_recipient,
+            pending_authority: Pubkey::default(),
+            crank_bounty_bps,
+            reveal_timeout,
+            randomness_commit_slot: 0,
+            reveal_slot: 0,
+            randomness_source,
         };
 
         Ok(())
@@ -53,9 +160,36 @@ pub mod token_lottery {
     /// - Create mint
     /// - Create metadata account
     /// - Verify the collection
-    pub fn initialize_lottery(ctx: Context<InitializeLottery>) -> Result<()> {
-        let signer_seeds: &[&[&[u8]]] =
-            &[&[b"collection_mint".as_ref(), &[ctx.bumps.collection_mint]]];
+    pub fn initialize_lottery(
+        ctx: Context<InitializeLottery>,
+        lottery_id: u64,
+        metadata: CollectionMetadata,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.payer.key() == ctx.accounts.token_lottery.authority,
+            ErrorCode::Unauthorized
+        );
+        require!(
+            matches!(
+                ctx.accounts.token_lottery.ticket_mode,
+                TicketMode::Nft | TicketMode::Compressed
+            ),
+            ErrorCode::WrongTicketMode
+        );
+        ctx.accounts.token_lottery.expect_status(
+            LotteryStatus::Configured,
+            ErrorCode::CollectionAlreadyInitialized,
+        )?;
+        metadata.validate()?;
+        ctx.accounts.token_lottery.status = LotteryStatus::CollectionInitialized;
+        ctx.accounts.token_lottery.metadata = metadata.clone();
+
+        let lottery_id_bytes = lottery_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"collection_mint".as_ref(),
+            lottery_id_bytes.as_ref(),
+            &[ctx.bumps.collection_mint],
+        ]];
 
         msg!("Creating mint account");
 
@@ -67,7 +201,7 @@ pub mod token_lottery {
                     to: ctx.accounts.collection_token_account.to_account_info(),
                     authority: ctx.accounts.collection_mint.to_account_info(),
                 },
-                &signer_seeds,
+                signer_seeds,
             ),
             1,
         )?;
@@ -85,12 +219,12 @@ pub mod token_lottery {
                     system_program: ctx.accounts.system_program.to_account_info(),
                     rent: ctx.accounts.rent.to_account_info(),
                 },
-                &signer_seeds,
+                signer_seeds,
             ),
             DataV2 {
-                name: NAME.to_string(),
-                symbol: SYMBOL.to_string(),
-                uri: URI.to_string(),
+                name: metadata.name,
+                symbol: metadata.symbol,
+                uri: metadata.uri,
                 seller_fee_basis_points: 0,
                 creators: Some(vec![Creator {
                     address: ctx.accounts.collection_mint.key(),
@@ -120,7 +254,7 @@ pub mod token_lottery {
                     system_program: ctx.accounts.system_program.to_account_info(),
                     rent: ctx.accounts.rent.to_account_info(),
                 },
-                &signer_seeds,
+                signer_seeds,
             ),
             Some(0),
         )?;
@@ -138,40 +272,57 @@ pub mod token_lottery {
         Ok(())
     }
 
-    pub fn buy_ticket(ctx: Context<BuyTicket>) -> Result<()> {
+    pub fn buy_ticket<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BuyTicket<'info>>,
+        lottery_id: u64,
+    ) -> Result<()> {
+        buy_tickets(ctx, lottery_id, 1)
+    }
+
+    /// Buys `quantity` consecutive tickets at once. They are issued as a single NFT and a
+    /// `TicketRecord` recording the first ticket number, the quantity and the mint, so the
+    /// buyer only pays for one set of ticket accounts.
+    pub fn buy_tickets<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BuyTicket<'info>>,
+        lottery_id: u64,
+        quantity: u64,
+    ) -> Result<()> {
         let clock = Clock::get()?;
-        let ticket_name = format!(
-            "{}{}",
-            NAME.to_owned(),
-            ctx.accounts
-                .token_lottery
-                .total_tickets
-                .to_string()
-                .as_str()
-        );
+        let first_ticket = ctx.accounts.token_lottery.total_tickets;
 
+        require!(quantity > 0, ErrorCode::InvalidTicketQuantity);
         require!(
-            clock.slot >= ctx.accounts.token_lottery.start_time,
-            ErrorCode::LotteryNotOpen
-        );
-        require!(
-            clock.slot < ctx.accounts.token_lottery.end_time,
-            ErrorCode::LotteryNotOpen
+            ctx.accounts.token_lottery.ticket_mode == TicketMode::Nft,
+            ErrorCode::WrongTicketMode
         );
 
-        system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                system_program::Transfer {
-                    from: ctx.accounts.payer.to_account_info(),
-                    to: ctx.accounts.token_lottery.to_account_info(),
-                },
-            ),
-            ctx.accounts.token_lottery.ticket_price,
+        ctx.accounts.token_lottery.expect_sales_open(clock.slot)?;
+
+        let ticket_name = ctx
+            .accounts
+            .token_lottery
+            .metadata
+            .ticket_name(first_ticket, quantity)?;
+        let ticket_uri = ctx.accounts.token_lottery.metadata.ticket_uri(first_ticket);
+
+        pay_for_tickets(
+            &mut ctx.accounts.token_lottery,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_mut(),
+            ctx.accounts.payer_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            ctx.remaining_accounts,
+            quantity,
         )?;
 
-        let signer_seeds: &[&[&[u8]]] =
-            &[&[b"collection_mint".as_ref(), &[ctx.bumps.collection_mint]]];
+        let lottery_id_bytes = lottery_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"collection_mint".as_ref(),
+            lottery_id_bytes.as_ref(),
+            &[ctx.bumps.collection_mint],
+        ]];
 
         mint_to(
             CpiContext::new_with_signer(
@@ -181,7 +332,7 @@ pub mod token_lottery {
                     to: ctx.accounts.destination.to_account_info(),
                     authority: ctx.accounts.collection_mint.to_account_info(),
                 },
-                &signer_seeds,
+                signer_seeds,
             ),
             1,
         )?;
@@ -199,12 +350,12 @@ pub mod token_lottery {
                     system_program: ctx.accounts.system_program.to_account_info(),
                     rent: ctx.accounts.rent.to_account_info(),
                 },
-                &signer_seeds,
+                signer_seeds,
             ),
             DataV2 {
                 name: ticket_name,
-                symbol: SYMBOL.to_string(),
-                uri: URI.to_string(),
+                symbol: ctx.accounts.token_lottery.metadata.symbol.clone(),
+                uri: ticket_uri,
                 seller_fee_basis_points: 0,
                 creators: None,
                 collection: None,
@@ -230,7 +381,7 @@ pub mod token_lottery {
                     system_program: ctx.accounts.system_program.to_account_info(),
                     rent: ctx.accounts.rent.to_account_info(),
                 },
-                &signer_seeds,
+                signer_seeds,
             ),
             Some(0),
         )?;
@@ -250,373 +401,2716 @@ pub mod token_lottery {
                         .collection_master_edition
                         .to_account_info(),
                 },
-                &signer_seeds,
+                signer_seeds,
             ),
             None,
         )?;
 
-        ctx.accounts.token_lottery.total_tickets += 1;
+        *ctx.accounts.ticket_record = TicketRecord {
+            lottery: ctx.accounts.token_lottery.key(),
+            index: first_ticket,
+            quantity,
+            mint: ctx.accounts.ticket_mint.key(),
+        };
+
+        ctx.accounts.token_lottery.total_tickets = first_ticket
+            .checked_add(quantity)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        Ok(())
+    }
+
+    /// Creates the ticket collection of a `Token2022` lottery: a Token-2022 mint holding
+    /// its own token metadata and token group, so no Metaplex program is involved.
+    pub fn initialize_token_2022_collection(
+        ctx: Context<InitializeToken2022Collection>,
+        lottery_id: u64,
+        metadata: CollectionMetadata,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.payer.key() == ctx.accounts.token_lottery.authority,
+            ErrorCode::Unauthorized
+        );
+        require!(
+            ctx.accounts.token_lottery.ticket_mode == TicketMode::Token2022,
+            ErrorCode::WrongTicketMode
+        );
+        ctx.accounts.token_lottery.expect_status(
+            LotteryStatus::Configured,
+            ErrorCode::CollectionAlreadyInitialized,
+        )?;
+        metadata.validate()?;
+        ctx.accounts.token_lottery.status = LotteryStatus::CollectionInitialized;
+        ctx.accounts.token_lottery.metadata = metadata.clone();
+
+        let collection_key = ctx.accounts.collection_mint.key();
+        let collection_info = ctx.accounts.collection_mint.to_account_info();
+        let collection_len = mint_len_with_metadata(
+            &collection_key,
+            &collection_key,
+            &[
+                ExtensionType::MetadataPointer,
+                ExtensionType::GroupPointer,
+                ExtensionType::TokenGroup,
+            ],
+            &metadata.name,
+            &metadata.symbol,
+            &metadata.uri,
+        )?;
+        fund_mint_growth(
+            &collection_info,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            collection_len,
+        )?;
+
+        let lottery_id_bytes = lottery_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"collection_mint".as_ref(),
+            lottery_id_bytes.as_ref(),
+            &[ctx.bumps.collection_mint],
+        ]];
+
+        msg!("Creating collection metadata");
+        token_metadata_initialize(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TokenMetadataInitialize {
+                    program_id: ctx.accounts.token_program.to_account_info(),
+                    metadata: collection_info.clone(),
+                    update_authority: collection_info.clone(),
+                    mint_authority: collection_info.clone(),
+                    mint: collection_info.clone(),
+                },
+                signer_seeds,
+            ),
+            metadata.name,
+            metadata.symbol,
+            metadata.uri,
+        )?;
+
+        msg!("Creating collection group");
+        token_group_initialize(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TokenGroupInitialize {
+                    program_id: ctx.accounts.token_program.to_account_info(),
+                    group: collection_info.clone(),
+                    mint: collection_info.clone(),
+                    mint_authority: collection_info,
+                },
+                signer_seeds,
+            ),
+            Some(collection_key),
+            u64::MAX,
+        )?;
 
         Ok(())
     }
 
-    pub fn commit_randomness(ctx: Context<CommitRandomness>) -> Result<()> {
+    /// Buys `quantity` consecutive tickets of a `Token2022` lottery, issued as a single
+    /// Token-2022 NFT whose token metadata names the range and whose group member
+    /// extension ties it to the collection.
+    pub fn buy_token_2022_tickets<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BuyToken2022Tickets<'info>>,
+        lottery_id: u64,
+        quantity: u64,
+    ) -> Result<()> {
         let clock = Clock::get()?;
+        let first_ticket = ctx.accounts.token_lottery.total_tickets;
 
-        let token_lottery = &mut ctx.accounts.token_lottery;
+        require!(quantity > 0, ErrorCode::InvalidTicketQuantity);
+        require!(
+            ctx.accounts.token_lottery.ticket_mode == TicketMode::Token2022,
+            ErrorCode::WrongTicketMode
+        );
+
+        ctx.accounts.token_lottery.expect_sales_open(clock.slot)?;
+
+        let ticket_name = ctx
+            .accounts
+            .token_lottery
+            .metadata
+            .ticket_name(first_ticket, quantity)?;
+        let ticket_uri = ctx.accounts.token_lottery.metadata.ticket_uri(first_ticket);
+
+        pay_for_tickets(
+            &mut ctx.accounts.token_lottery,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_mut(),
+            ctx.accounts.payer_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            ctx.remaining_accounts,
+            quantity,
+        )?;
+
+        let ticket_info = ctx.accounts.ticket_mint.to_account_info();
+        let collection_info = ctx.accounts.collection_mint.to_account_info();
+        let ticket_len = mint_len_with_metadata(
+            ticket_info.key,
+            collection_info.key,
+            &[
+                ExtensionType::MetadataPointer,
+                ExtensionType::GroupMemberPointer,
+                ExtensionType::TokenGroupMember,
+            ],
+            &ticket_name,
+            &ctx.accounts.token_lottery.metadata.symbol,
+            &ticket_uri,
+        )?;
+        fund_mint_growth(
+            &ticket_info,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            ticket_len,
+        )?;
+
+        let lottery_id_bytes = lottery_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"collection_mint".as_ref(),
+            lottery_id_bytes.as_ref(),
+            &[ctx.bumps.collection_mint],
+        ]];
+
+        msg!("Creating ticket metadata");
+        token_metadata_initialize(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TokenMetadataInitialize {
+                    program_id: ctx.accounts.token_program.to_account_info(),
+                    metadata: ticket_info.clone(),
+                    update_authority: collection_info.clone(),
+                    mint_authority: collection_info.clone(),
+                    mint: ticket_info.clone(),
+                },
+                signer_seeds,
+            ),
+            ticket_name,
+            ctx.accounts.token_lottery.metadata.symbol.clone(),
+            ticket_uri,
+        )?;
+
+        msg!("Adding ticket to the collection group");
+        token_member_initialize(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TokenMemberInitialize {
+                program_id: ctx.accounts.token_program.to_account_info(),
+                member: ticket_info.clone(),
+                member_mint: ticket_info.clone(),
+                member_mint_authority: collection_info.clone(),
+                group: collection_info.clone(),
+                group_update_authority: collection_info.clone(),
+            },
+            signer_seeds,
+        ))?;
+
+        mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ticket_info.clone(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: collection_info.clone(),
+                },
+                signer_seeds,
+            ),
+            1,
+        )?;
+
+        // Without a master edition, dropping the mint authority is what keeps the supply at 1
+        set_authority(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                SetAuthority {
+                    current_authority: collection_info,
+                    account_or_mint: ticket_info,
+                },
+                signer_seeds,
+            ),
+            AuthorityType::MintTokens,
+            None,
+        )?;
+
+        *ctx.accounts.ticket_record = TicketRecord {
+            lottery: ctx.accounts.token_lottery.key(),
+            index: first_ticket,
+            quantity,
+            mint: ctx.accounts.ticket_mint.key(),
+        };
+
+        ctx.accounts.token_lottery.total_tickets = first_ticket
+            .checked_add(quantity)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        Ok(())
+    }
 
+    /// Creates the ticket collection of a `Core` lottery: an MPL Core collection whose
+    /// update authority is the lottery PDA.
+    pub fn initialize_core_collection(
+        ctx: Context<InitializeCoreCollection>,
+        lottery_id: u64,
+        metadata: CollectionMetadata,
+    ) -> Result<()> {
         require!(
-            ctx.accounts.payer.key() == token_lottery.authority,
+            ctx.accounts.payer.key() == ctx.accounts.token_lottery.authority,
             ErrorCode::Unauthorized
         );
+        require!(
+            ctx.accounts.token_lottery.ticket_mode == TicketMode::Core,
+            ErrorCode::WrongTicketMode
+        );
+        ctx.accounts.token_lottery.expect_status(
+            LotteryStatus::Configured,
+            ErrorCode::CollectionAlreadyInitialized,
+        )?;
+        metadata.validate()?;
+        ctx.accounts.token_lottery.status = LotteryStatus::CollectionInitialized;
+        ctx.accounts.token_lottery.metadata = metadata.clone();
+
+        let lottery_id_bytes = lottery_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"core_collection".as_ref(),
+            lottery_id_bytes.as_ref(),
+            &[ctx.bumps.core_collection],
+        ]];
+
+        msg!("Creating Core collection");
+        CreateCollectionV2CpiBuilder::new(&ctx.accounts.mpl_core_program.to_account_info())
+            .collection(&ctx.accounts.core_collection.to_account_info())
+            .update_authority(Some(&ctx.accounts.token_lottery.to_account_info()))
+            .payer(&ctx.accounts.payer.to_account_info())
+            .system_program(&ctx.accounts.system_program.to_account_info())
+            .name(metadata.name)
+            .uri(metadata.uri)
+            .invoke_signed(signer_seeds)?;
+
+        Ok(())
+    }
 
-        let randomness_data =
-            RandomnessAccountData::parse(ctx.accounts.randomness_account.data.borrow()).unwrap();
+    /// Buys `quantity` consecutive tickets of a `Core` lottery, issued as a single MPL
+    /// Core asset in the lottery's collection. The range is recorded in the asset's
+    /// attributes, so no other account is created.
+    pub fn buy_core_tickets<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BuyCoreTickets<'info>>,
+        lottery_id: u64,
+        quantity: u64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let first_ticket = ctx.accounts.token_lottery.total_tickets;
 
-        // if we already passed the slot it means the data was already revealed and someone
-        // could know who the winner would be
+        require!(quantity > 0, ErrorCode::InvalidTicketQuantity);
         require!(
-            randomness_data.seed_slot == clock.slot - 1,
-            ErrorCode::RandomnessAlreadyRevealed
+            ctx.accounts.token_lottery.ticket_mode == TicketMode::Core,
+            ErrorCode::WrongTicketMode
         );
 
-        token_lottery.randomness_account = ctx.accounts.randomness_account.key();
+        ctx.accounts.token_lottery.expect_sales_open(clock.slot)?;
+
+        pay_for_tickets(
+            &mut ctx.accounts.token_lottery,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_mut(),
+            ctx.accounts.payer_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            ctx.remaining_accounts,
+            quantity,
+        )?;
+
+        // The asset PDA signs its own creation, the lottery PDA as collection authority
+        let lottery_id_bytes = lottery_id.to_le_bytes();
+        let first_ticket_bytes = first_ticket.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[
+            &[
+                lottery_id_bytes.as_ref(),
+                first_ticket_bytes.as_ref(),
+                &[ctx.bumps.ticket_asset],
+            ],
+            &[
+                b"token_lottery".as_ref(),
+                lottery_id_bytes.as_ref(),
+                &[ctx.accounts.token_lottery.bump],
+            ],
+        ];
+
+        msg!("Creating Core ticket asset");
+        CreateV2CpiBuilder::new(&ctx.accounts.mpl_core_program.to_account_info())
+            .asset(&ctx.accounts.ticket_asset.to_account_info())
+            .collection(Some(&ctx.accounts.core_collection.to_account_info()))
+            .authority(Some(&ctx.accounts.token_lottery.to_account_info()))
+            .payer(&ctx.accounts.payer.to_account_info())
+            .owner(Some(&ctx.accounts.payer.to_account_info()))
+            .system_program(&ctx.accounts.system_program.to_account_info())
+            .name(
+                ctx.accounts
+                    .token_lottery
+                    .metadata
+                    .ticket_name(first_ticket, quantity)?,
+            )
+            .uri(ctx.accounts.token_lottery.metadata.ticket_uri(first_ticket))
+            .plugins(vec![ticket_attributes(first_ticket, quantity)])
+            .invoke_signed(signer_seeds)?;
+
+        ctx.accounts.token_lottery.total_tickets = first_ticket
+            .checked_add(quantity)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
 
         Ok(())
     }
 
-    pub fn reveal_winner(ctx: Context<RevealWinner>) -> Result<()> {
-        let clock = Clock::get()?;
-        let token_lottery = &mut ctx.accounts.token_lottery;
+    /// Creates the Bubblegum tree config of a compressed-ticket lottery. The Merkle tree
+    /// account must already be allocated for `max_depth` and `max_buffer_size` and owned by
+    /// the account compression program. The lottery PDA becomes the tree creator, so only
+    /// the lottery can mint into it.
+    pub fn initialize_ticket_tree(
+        ctx: Context<InitializeTicketTree>,
+        _lottery_id: u64,
+        max_depth: u32,
+        max_buffer_size: u32,
+    ) -> Result<()> {
+        let token_lottery = &ctx.accounts.token_lottery;
 
         require!(
             ctx.accounts.payer.key() == token_lottery.authority,
             ErrorCode::Unauthorized
         );
-
         require!(
-            ctx.accounts.randomness_account.key() == token_lottery.randomness_account,
-            ErrorCode::RandomnessAlreadyRevealed
+            token_lottery.ticket_mode == TicketMode::Compressed,
+            ErrorCode::WrongTicketMode
         );
-
         require!(
-            clock.slot >= token_lottery.end_time,
-            ErrorCode::LotteryNotCompleted
+            token_lottery.merkle_tree == Pubkey::default(),
+            ErrorCode::TicketTreeAlreadyInitialized
         );
 
-        require!(!token_lottery.winner_chosen, ErrorCode::WinnerChosen);
+        let lottery_id_bytes = token_lottery.lottery_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"token_lottery".as_ref(),
+            lottery_id_bytes.as_ref(),
+            &[token_lottery.bump],
+        ]];
+
+        CreateTreeConfigCpiBuilder::new(&ctx.accounts.bubblegum_program.to_account_info())
+            .tree_config(&ctx.accounts.tree_config.to_account_info())
+            .merkle_tree(&ctx.accounts.merkle_tree.to_account_info())
+            .payer(&ctx.accounts.payer.to_account_info())
+            .tree_creator(&ctx.accounts.token_lottery.to_account_info())
+            .log_wrapper(&ctx.accounts.log_wrapper.to_account_info())
+            .compression_program(&ctx.accounts.compression_program.to_account_info())
+            .system_program(&ctx.accounts.system_program.to_account_info())
+            .max_depth(max_depth)
+            .max_buffer_size(max_buffer_size)
+            .public(false)
+            .invoke_signed(signer_seeds)?;
+
+        ctx.accounts.token_lottery.merkle_tree = ctx.accounts.merkle_tree.key();
+
+        Ok(())
+    }
+
+    /// Buys one ticket minted as a compressed NFT into the lottery's Merkle tree, so the
+    /// buyer pays no rent for ticket accounts. Each ticket is its own leaf, so the leaf
+    /// nonce is the ticket number.
+    pub fn buy_compressed_ticket<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BuyCompressedTicket<'info>>,
+        lottery_id: u64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let ticket = ctx.accounts.token_lottery.total_tickets;
+
+        require!(
+            ctx.accounts.token_lottery.ticket_mode == TicketMode::Compressed,
+            ErrorCode::WrongTicketMode
+        );
+        require!(
+            ctx.accounts.token_lottery.merkle_tree != Pubkey::default(),
+            ErrorCode::TicketTreeNotInitialized
+        );
 
-        let randomness_data =
-            RandomnessAccountData::parse(ctx.accounts.randomness_account.data.borrow()).unwrap();
+        ctx.accounts.token_lottery.expect_sales_open(clock.slot)?;
+
+        pay_for_tickets(
+            &mut ctx.accounts.token_lottery,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_mut(),
+            ctx.accounts.payer_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            ctx.remaining_accounts,
+            1,
+        )?;
 
-        let reveal_random_value = randomness_data
-            .get_value(clock.slot)
-            .map_err(|_| ErrorCode::RandomnessNotResolved)?;
+        // The lottery PDA signs as tree creator, the collection mint as collection authority
+        let lottery_id_bytes = lottery_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[
+            &[
+                b"token_lottery".as_ref(),
+                lottery_id_bytes.as_ref(),
+                &[ctx.accounts.token_lottery.bump],
+            ],
+            &[
+                b"collection_mint".as_ref(),
+                lottery_id_bytes.as_ref(),
+                &[ctx.bumps.collection_mint],
+            ],
+        ];
+
+        msg!("Minting compressed ticket {}", ticket);
+        MintToCollectionV1CpiBuilder::new(&ctx.accounts.bubblegum_program.to_account_info())
+            .tree_config(&ctx.accounts.tree_config.to_account_info())
+            .leaf_owner(&ctx.accounts.payer.to_account_info())
+            .leaf_delegate(&ctx.accounts.payer.to_account_info())
+            .merkle_tree(&ctx.accounts.merkle_tree.to_account_info())
+            .payer(&ctx.accounts.payer.to_account_info())
+            .tree_creator_or_delegate(&ctx.accounts.token_lottery.to_account_info())
+            .collection_authority(&ctx.accounts.collection_mint.to_account_info())
+            .collection_mint(&ctx.accounts.collection_mint.to_account_info())
+            .collection_metadata(&ctx.accounts.collection_metadata.to_account_info())
+            .collection_edition(&ctx.accounts.collection_master_edition.to_account_info())
+            .bubblegum_signer(&ctx.accounts.bubblegum_signer.to_account_info())
+            .log_wrapper(&ctx.accounts.log_wrapper.to_account_info())
+            .compression_program(&ctx.accounts.compression_program.to_account_info())
+            .token_metadata_program(&ctx.accounts.token_metadata_program.to_account_info())
+            .system_program(&ctx.accounts.system_program.to_account_info())
+            .metadata(MetadataArgs {
+                name: ctx.accounts.token_lottery.metadata.ticket_name(ticket, 1)?,
+                symbol: ctx.accounts.token_lottery.metadata.symbol.clone(),
+                uri: ctx.accounts.token_lottery.metadata.ticket_uri(ticket),
+                seller_fee_basis_points: 0,
+                primary_sale_happened: false,
+                is_mutable: true,
+                edition_nonce: None,
+                token_standard: Some(TokenStandard::NonFungible),
+                collection: Some(Collection {
+                    verified: false,
+                    key: ctx.accounts.collection_mint.key(),
+                }),
+                uses: None,
+                token_program_version: TokenProgramVersion::Original,
+                creators: Vec::new(),
+            })
+            .invoke_signed(signer_seeds)?;
 
-        let winner = reveal_random_value[0] as u64 % token_lottery.total_tickets;
-        token_lottery.winner = winner;
-        token_lottery.winner_chosen = true;
+        ctx.accounts.token_lottery.total_tickets =
+            ticket.checked_add(1).ok_or(ErrorCode::ArithmeticOverflow)?;
 
         Ok(())
     }
 
-    pub fn claim_winnings(ctx: Context<ClaimWinnings>) -> Result<()> {
-        require!(
-            ctx.accounts.token_lottery.winner_chosen,
-            ErrorCode::WinnerNotChosen
-        );
+    /// Creates the registry recording ticket owners of a `Ledger` lottery. It takes the
+    /// place of the ticket collection, so `initialize_lottery` is not used in this mode.
+    pub fn initialize_ticket_registry(
+        ctx: Context<InitializeTicketRegistry>,
+        lottery_id: u64,
+    ) -> Result<()> {
+        let token_lottery = &mut ctx.accounts.token_lottery;
 
-        // Check if token is a part of the collection
         require!(
-            ctx.accounts
-                .ticket_metadata
-                .collection
-                .as_ref()
-                .unwrap()
-                .verified,
-            ErrorCode::NotVerifiedTicket
+            ctx.accounts.payer.key() == token_lottery.authority,
+            ErrorCode::Unauthorized
         );
         require!(
-            ctx.accounts
-                .ticket_metadata
-                .collection
-                .as_ref()
-                .unwrap()
-                .key
-                == ctx.accounts.collection_mint.key(),
-            ErrorCode::IncorrectTicket
+            token_lottery.ticket_mode == TicketMode::Ledger,
+            ErrorCode::WrongTicketMode
         );
+        token_lottery.expect_status(
+            LotteryStatus::Configured,
+            ErrorCode::CollectionAlreadyInitialized,
+        )?;
+        token_lottery.status = LotteryStatus::CollectionInitialized;
 
-        let ticket_name = format!(
-            "{}{}",
-            NAME.to_owned(),
-            ctx.accounts.token_lottery.winner.to_string()
-        );
+        let mut ticket_registry = ctx.accounts.ticket_registry.load_init()?;
+        ticket_registry.lottery_id = lottery_id;
+        ticket_registry.len = 0;
 
-        let metadata_name = ctx.accounts.ticket_metadata.name.replace("\u{0}", "");
+        Ok(())
+    }
 
-        msg!("Ticket name: {}", ticket_name);
-        msg!("Metdata name: {}", metadata_name);
+    /// Buys `quantity` tickets of a `Ledger` lottery by appending the buyer to the ticket
+    /// registry once per ticket. No mint, metadata or token account is created.
+    pub fn buy_ledger_tickets<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BuyLedgerTickets<'info>>,
+        _lottery_id: u64,
+        quantity: u64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let first_ticket = ctx.accounts.token_lottery.total_tickets;
 
-        // Check if the winner has the winning ticket
-        require!(metadata_name == ticket_name, ErrorCode::IncorrectTicket);
         require!(
-            ctx.accounts.ticket_account.amount > 0,
-            ErrorCode::IncorrectTicket
+            quantity > 0 && quantity <= MAX_LEDGER_TICKETS_PER_PURCHASE,
+            ErrorCode::InvalidTicketQuantity
+        );
+        require!(
+            ctx.accounts.token_lottery.ticket_mode == TicketMode::Ledger,
+            ErrorCode::WrongTicketMode
         );
 
-        **ctx
-            .accounts
-            .token_lottery
-            .to_account_info()
-            .try_borrow_mut_lamports()? -= ctx.accounts.token_lottery.lottery_pot_amount;
-        **ctx.accounts.payer.try_borrow_mut_lamports()? +=
-            ctx.accounts.token_lottery.lottery_pot_amount;
+        ctx.accounts.token_lottery.expect_sales_open(clock.slot)?;
+
+        pay_for_tickets(
+            &mut ctx.accounts.token_lottery,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_mut(),
+            ctx.accounts.payer_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            ctx.remaining_accounts,
+            quantity,
+        )?;
+
+        let total_tickets = first_ticket
+            .checked_add(quantity)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        // The registry was grown to `total_tickets` entries by the realloc constraint
+        ctx.accounts.ticket_registry.load_mut()?.len = total_tickets;
+        write_entries(
+            &mut ctx
+                .accounts
+                .ticket_registry
+                .as_ref()
+                .try_borrow_mut_data()?,
+            first_ticket,
+            ctx.accounts.payer.key,
+            quantity,
+        )?;
 
-        ctx.accounts.token_lottery.lottery_pot_amount = 0;
+        ctx.accounts.token_lottery.total_tickets = total_tickets;
 
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
+    /// Commits the Switchboard randomness the winners will be drawn from. Anyone can call it
+    /// once sales are closed and is paid the crank bounty.
+    ///
+    /// If the committed randomness is still unrevealed `reveal_timeout` slots after its
+    /// commit, it can be replaced by passing it as `stale_randomness_account`. If it can no
+    /// longer be revealed at all (committed again on Switchboard, or closed), the lottery
+    /// moves to refunds instead.
+    pub fn commit_randomness<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CommitRandomness<'info>>,
+        _lottery_id: u64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
 
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + TokenLottery::INIT_SPACE,
-        seeds = [b"token_lottery".as_ref()],
-        bump
-    )]
-    pub token_lottery: Account<'info, TokenLottery>,
+        let token_lottery = &mut ctx.accounts.token_lottery;
 
-    pub system_program: Program<'info, System>,
-}
+        token_lottery.sync_status(clock.slot);
 
-#[derive(Accounts)]
-pub struct InitializeLottery<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
+        let recommit = token_lottery.status == LotteryStatus::RandomnessCommitted;
+        if recommit {
+            require!(
+                clock.slot >= token_lottery.reveal_deadline(),
+                ErrorCode::RevealDeadlineNotReached
+            );
 
-    #[account(
-        init,
-        payer = payer,
-        mint::decimals = 0,
-        mint::authority = collection_mint,
-        mint::freeze_authority = collection_mint,
-        seeds = [b"collection_mint".as_ref()],
-        bump
-    )]
-    pub collection_mint: InterfaceAccount<'info, Mint>,
+            let stale_randomness_account = ctx
+                .accounts
+                .stale_randomness_account
+                .as_ref()
+                .ok_or(ErrorCode::CannotRecommitRandomness)?;
+
+            // The committed randomness will never resolve. A redraw would let whoever
+            // tampered with it choose between outcomes, so the tickets are refunded instead
+            if token_lottery.randomness_source.is_abandoned(
+                stale_randomness_account,
+                token_lottery.randomness_commit_slot,
+            ) {
+                emit!(RandomnessAbandoned {
+                    lottery_id: token_lottery.lottery_id,
+                    randomness_account: token_lottery.randomness_account,
+                    slot: clock.slot,
+                });
+                token_lottery.status = LotteryStatus::Refunding;
+                return Ok(());
+            }
+
+            token_lottery.randomness_source.ensure_unrevealed(
+                stale_randomness_account,
+                token_lottery.randomness_commit_slot,
+            )?;
+        } else {
+            token_lottery.expect_sales_closed(clock.slot)?;
+            if token_lottery.settle_without_participants(clock.slot) {
+                return Ok(());
+            }
+        }
+
+        token_lottery
+            .randomness_source
+            .commit(&ctx.accounts.randomness_account, clock.slot)?;
+
+        if recommit {
+            emit!(RandomnessRecommitted {
+                lottery_id: token_lottery.lottery_id,
+                stale_randomness_account: token_lottery.randomness_account,
+                randomness_account: ctx.accounts.randomness_account.key(),
+                slot: clock.slot,
+            });
+        }
 
-    #[account(
-        init,
-        payer = payer,
-        token::mint = collection_mint,
-        token::authority = collection_token_account,
-        seeds = [b"collection_associated_token".as_ref()],
-        bump
-    )]
-    pub collection_token_account: InterfaceAccount<'info, TokenAccount>,
+        token_lottery.randomness_account = ctx.accounts.randomness_account.key();
+        token_lottery.randomness_commit_slot = clock.slot;
+        token_lottery.status = LotteryStatus::RandomnessCommitted;
+
+        // The bounty is paid once: replacing randomness the committer withheld earns nothing
+        if !recommit && token_lottery.crank_reward() > 0 {
+            let token_payment = TokenPayment::for_lottery(
+                &ctx.accounts.token_lottery,
+                ctx.accounts.payment_mint.as_ref(),
+                ctx.accounts.vault.as_ref(),
+                ctx.accounts.payer_token_account.as_ref(),
+                ctx.accounts.payment_token_program.as_ref(),
+                ctx.remaining_accounts,
+            )?;
+            pay_crank_bounty(
+                &mut ctx.accounts.token_lottery,
+                &ctx.accounts.payer.to_account_info(),
+                token_payment,
+            )?;
+        }
 
-    #[account(
-        mut,
-        seeds = [b"metadata", token_metadata_program.key().as_ref(), collection_mint.key().as_ref()],
-        bump,
-        seeds::program = token_metadata_program.key(),
-    )]
-    /// CHECK: Checked by the metadata smart contract
-    pub metadata: UncheckedAccount<'info>,
+        Ok(())
+    }
 
-    #[account(
-        mut,
-        seeds = [b"metadata", token_metadata_program.key().as_ref(), collection_mint.key().as_ref(), b"edition".as_ref()],
-        bump,
-        seeds::program = token_metadata_program.key(),
-    )]
-    /// CHECK: Checked by the metadata smart contract
-    pub master_edition: UncheckedAccount<'info>,
+    /// Draws the winners from the committed randomness once it is resolved. Anyone can
+    /// call it and is paid the crank bounty before the prize pool is taken.
+    pub fn reveal_winner<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RevealWinner<'info>>,
+        _lottery_id: u64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let token_lottery = &mut ctx.accounts.token_lottery;
 
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_metadata_program: Program<'info, Metadata>,
-    pub token_program: Interface<'info, TokenInterface>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+        token_lottery.sync_status(clock.slot);
 
-#[derive(Accounts)]
-pub struct BuyTicket<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
+        if token_lottery.settle_without_participants(clock.slot) {
+            return Ok(());
+        }
 
-    #[account(
-        mut,
-        seeds = [b"token_lottery".as_ref()],
-        bump = token_lottery.bump,
-    )]
+        token_lottery.expect_randomness_committed()?;
+        require!(
+            ctx.accounts.randomness_account.key() == token_lottery.randomness_account,
+            ErrorCode::RandomnessAlreadyRevealed
+        );
+
+        let reveal_random_value = token_lottery.randomness_source.revealed_value(
+            &ctx.accounts.randomness_account,
+            token_lottery.randomness_commit_slot,
+            clock.slot,
+        )?;
+
+        if token_lottery.crank_reward() > 0 {
+            let token_payment = TokenPayment::for_lottery(
+                &ctx.accounts.token_lottery,
+                ctx.accounts.payment_mint.as_ref(),
+                ctx.accounts.vault.as_ref(),
+                ctx.accounts.payer_token_account.as_ref(),
+                ctx.accounts.payment_token_program.as_ref(),
+                ctx.remaining_accounts,
+            )?;
+            pay_crank_bounty(
+                &mut ctx.accounts.token_lottery,
+                &ctx.accounts.payer.to_account_info(),
+                token_payment,
+            )?;
+        }
+        ctx.accounts
+            .token_lottery
+            .record_draw(&reveal_random_value, clock.slot);
+
+        Ok(())
+    }
+
+    /// Reveals the secret of a `CommitReveal` lottery and draws the winners from it, mixed
+    /// with the hash of a slot produced after the commit. Anyone holding the secret can
+    /// call it until the reveal deadline, after which the lottery refunds its tickets. The
+    /// secret holder sees the outcome before revealing, so this source trusts the operator.
+    pub fn reveal_secret(
+        ctx: Context<RevealSecret>,
+        _lottery_id: u64,
+        secret: [u8; 32],
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let token_lottery = &mut ctx.accounts.token_lottery;
+
+        token_lottery.sync_status(clock.slot);
+        token_lottery.expect_randomness_committed()?;
+
+        let reveal_random_value = token_lottery.randomness_source.value_from_secret(
+            &secret,
+            &ctx.accounts.randomness_account,
+            token_lottery.randomness_commit_slot,
+        )?;
+        token_lottery.record_draw(&reveal_random_value, clock.slot);
+
+        Ok(())
+    }
+
+    pub fn claim_winnings<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimWinnings<'info>>,
+        _lottery_id: u64,
+        tier: u8,
+        _first_ticket: u64,
+    ) -> Result<()> {
+        ctx.accounts.token_lottery.expect_prize_claimable(tier)?;
+
+        // The ticket record holds the winning ticket and names the mint, so holding the
+        // mint is holding the winning ticket
+        require!(
+            ctx.accounts.ticket_account.amount > 0,
+            ErrorCode::IncorrectTicket
+        );
+
+        claim_prize(
+            &mut ctx.accounts.token_lottery,
+            &ctx.accounts.payer.to_account_info(),
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_ref(),
+            ctx.accounts.winner_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            ctx.remaining_accounts,
+            tier,
+        )
+    }
+
+    /// Claims a prize tier with a compressed ticket. `proof` shows that the payer owns the
+    /// leaf of the winning ticket in the lottery's Merkle tree.
+    pub fn claim_compressed_winnings<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimCompressedWinnings<'info>>,
+        _lottery_id: u64,
+        tier: u8,
+        proof: TicketLeafProof,
+    ) -> Result<()> {
+        ctx.accounts.token_lottery.expect_prize_claimable(tier)?;
+
+        let (proof_accounts, hook_accounts) =
+            proof.split_remaining_accounts(ctx.remaining_accounts)?;
+        verify_ticket_leaf(
+            &ctx.accounts.compression_program.to_account_info(),
+            &ctx.accounts.merkle_tree.to_account_info(),
+            proof_accounts,
+            ctx.accounts.payer.key(),
+            ctx.accounts.leaf_delegate.key(),
+            ctx.accounts.token_lottery.winners[tier as usize],
+            &proof,
+        )?;
+
+        claim_prize(
+            &mut ctx.accounts.token_lottery,
+            &ctx.accounts.payer.to_account_info(),
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_ref(),
+            ctx.accounts.winner_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            hook_accounts,
+            tier,
+        )
+    }
+
+    /// Claims a prize tier of a `Ledger` lottery. The signer must be the registry entry
+    /// of the winning ticket.
+    pub fn claim_ledger_winnings<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimLedgerWinnings<'info>>,
+        _lottery_id: u64,
+        tier: u8,
+    ) -> Result<()> {
+        ctx.accounts.token_lottery.expect_prize_claimable(tier)?;
+
+        let winner = read_entry(
+            &ctx.accounts.ticket_registry.as_ref().try_borrow_data()?,
+            ctx.accounts.token_lottery.winners[tier as usize],
+        )?;
+        require!(
+            winner == ctx.accounts.payer.key(),
+            ErrorCode::IncorrectTicket
+        );
+
+        claim_prize(
+            &mut ctx.accounts.token_lottery,
+            &ctx.accounts.payer.to_account_info(),
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_ref(),
+            ctx.accounts.winner_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            ctx.remaining_accounts,
+            tier,
+        )
+    }
+
+    /// Claims a prize tier of a `Core` lottery. The signer must own the Core asset whose
+    /// recorded range holds the tier's winning ticket.
+    pub fn claim_core_winnings<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimCoreWinnings<'info>>,
+        _lottery_id: u64,
+        tier: u8,
+        _first_ticket: u64,
+    ) -> Result<()> {
+        ctx.accounts.token_lottery.expect_prize_claimable(tier)?;
+
+        let tickets = verify_ticket_asset(
+            &ctx.accounts.ticket_asset.to_account_info(),
+            &ctx.accounts.core_collection.key(),
+            &ctx.accounts.payer.key(),
+        )?;
+        require!(
+            tickets.contains(&ctx.accounts.token_lottery.winners[tier as usize]),
+            ErrorCode::IncorrectTicket
+        );
+
+        claim_prize(
+            &mut ctx.accounts.token_lottery,
+            &ctx.accounts.payer.to_account_info(),
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_ref(),
+            ctx.accounts.winner_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            ctx.remaining_accounts,
+            tier,
+        )
+    }
+
+    /// Marks the outcome on a ticket NFT once the winners are drawn (anyone can call it).
+    /// A winning ticket is renamed `<name> #first-last Winner` and switched to the winner
+    /// URI; a losing ticket is switched to the loser URI, if the lottery has one. Token-2022
+    /// tickets also get an `outcome` metadata field.
+    pub fn mark_winner_metadata(
+        ctx: Context<MarkWinnerMetadata>,
+        lottery_id: u64,
+        first_ticket: u64,
+    ) -> Result<()> {
+        let token_lottery = &ctx.accounts.token_lottery;
+        require!(
+            matches!(
+                token_lottery.status,
+                LotteryStatus::WinnerRevealed | LotteryStatus::Claimed
+            ),
+            ErrorCode::WinnerNotChosen
+        );
+
+        let ticket_record = &ctx.accounts.ticket_record;
+        let quantity = ticket_record.quantity;
+        let is_winner = token_lottery
+            .winners
+            .iter()
+            .any(|winner| ticket_record.contains(*winner));
+
+        let metadata = &token_lottery.metadata;
+        let (name, uri, outcome) = if is_winner {
+            (
+                metadata.winning_ticket_name(first_ticket, quantity)?,
+                metadata
+                    .winner_uri(first_ticket)
+                    .unwrap_or_else(|| metadata.ticket_uri(first_ticket)),
+                "winner",
+            )
+        } else {
+            (
+                metadata.ticket_name(first_ticket, quantity)?,
+                metadata
+                    .loser_uri(first_ticket)
+                    .ok_or(ErrorCode::LosingTicketsNotMarked)?,
+                "loser",
+            )
+        };
+
+        let lottery_id_bytes = lottery_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"collection_mint".as_ref(),
+            lottery_id_bytes.as_ref(),
+            &[ctx.bumps.collection_mint],
+        ]];
+
+        match token_lottery.ticket_mode {
+            TicketMode::Nft => {
+                let ticket_metadata = ctx
+                    .accounts
+                    .ticket_metadata
+                    .as_ref()
+                    .ok_or(ErrorCode::MissingMetadataAccounts)?;
+                let token_metadata_program = ctx
+                    .accounts
+                    .token_metadata_program
+                    .as_ref()
+                    .ok_or(ErrorCode::MissingMetadataAccounts)?;
+
+                update_metadata_accounts_v2(
+                    CpiContext::new_with_signer(
+                        token_metadata_program.to_account_info(),
+                        UpdateMetadataAccountsV2 {
+                            metadata: ticket_metadata.to_account_info(),
+                            update_authority: ctx.accounts.collection_mint.to_account_info(),
+                        },
+                        signer_seeds,
+                    ),
+                    None,
+                    Some(DataV2 {
+                        name,
+                        symbol: ticket_metadata.symbol.replace("\u{0}", ""),
+                        uri,
+                        seller_fee_basis_points: ticket_metadata.seller_fee_basis_points,
+                        creators: ticket_metadata.creators.clone(),
+                        collection: ticket_metadata.collection.clone(),
+                        uses: ticket_metadata.uses.clone(),
+                    }),
+                    None,
+                    None,
+                )?;
+            }
+            TicketMode::Token2022 => {
+                let ticket_info = ctx.accounts.ticket_mint.to_account_info();
+                let updates = [
+                    (Field::Name, name),
+                    (Field::Uri, uri),
+                    (Field::Key("outcome".to_string()), outcome.to_string()),
+                ];
+
+                let ticket_len = mint_len_after_update(&ticket_info, &updates)?;
+                fund_mint_growth(
+                    &ticket_info,
+                    &ctx.accounts.payer,
+                    &ctx.accounts.system_program,
+                    ticket_len,
+                )?;
+
+                for (field, value) in updates {
+                    token_metadata_update_field(
+                        CpiContext::new_with_signer(
+                            ctx.accounts.token_program.to_account_info(),
+                            TokenMetadataUpdateField {
+                                program_id: ctx.accounts.token_program.to_account_info(),
+                                metadata: ticket_info.clone(),
+                                update_authority: ctx.accounts.collection_mint.to_account_info(),
+                            },
+                            signer_seeds,
+                        ),
+                        field,
+                        value,
+                    )?;
+                }
+            }
+            TicketMode::Compressed | TicketMode::Ledger | TicketMode::Core => {
+                return err!(ErrorCode::WrongTicketMode);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Burns a ticket NFT of an `Nft` lottery once the winners are drawn, through Metaplex
+    /// `burn_nft`, and returns the rent of its token account, metadata, master edition and
+    /// ticket record to the holder. A winning ticket can only be burned once its prize
+    /// is claimed. Tickets can still be burned after `close_lottery`, since nothing is
+    /// left to claim by then.
+    pub fn burn_ticket(
+        ctx: Context<BurnTicket>,
+        _lottery_id: u64,
+        _first_ticket: u64,
+    ) -> Result<()> {
+        if ctx.accounts.token_lottery.owner == &ID {
+            let token_lottery = TokenLottery::try_deserialize(
+                &mut &ctx.accounts.token_lottery.try_borrow_data()?[..],
+            )?;
+            require!(
+                token_lottery.ticket_mode == TicketMode::Nft,
+                ErrorCode::WrongTicketMode
+            );
+            require!(
+                matches!(
+                    token_lottery.status,
+                    LotteryStatus::WinnerRevealed | LotteryStatus::Claimed
+                ),
+                ErrorCode::WinnerNotChosen
+            );
+
+            let unclaimed_prize = token_lottery
+                .winners
+                .iter()
+                .enumerate()
+                .any(|(tier, winner)| {
+                    ctx.accounts.ticket_record.contains(*winner)
+                        && !token_lottery.is_tier_claimed(tier as u8)
+                });
+            require!(!unclaimed_prize, ErrorCode::PrizeNotClaimed);
+        }
+
+        // The ticket is a verified member of the sized collection, whose size Metaplex
+        // decrements through the collection metadata
+        burn_nft(
+            CpiContext::new(
+                ctx.accounts.token_metadata_program.to_account_info(),
+                BurnNft {
+                    metadata: ctx.accounts.ticket_metadata.to_account_info(),
+                    owner: ctx.accounts.payer.to_account_info(),
+                    mint: ctx.accounts.ticket_mint.to_account_info(),
+                    token: ctx.accounts.ticket_account.to_account_info(),
+                    edition: ctx.accounts.ticket_master_edition.to_account_info(),
+                    spl_token: ctx.accounts.token_program.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(vec![ctx.accounts.collection_metadata.to_account_info()]),
+            Some(ctx.accounts.collection_metadata.key()),
+        )
+    }
+
+    /// Proposes a new authority for the lottery (authority only). The proposal only takes
+    /// effect once the new authority signs `accept_authority`, so a mistyped key can't
+    /// lock the lottery; proposing `Pubkey::default()` withdraws a pending proposal.
+    pub fn propose_authority(
+        ctx: Context<ProposeAuthority>,
+        _lottery_id: u64,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let token_lottery = &mut ctx.accounts.token_lottery;
+
+        require!(
+            ctx.accounts.payer.key() == token_lottery.authority,
+            ErrorCode::Unauthorized
+        );
+
+        token_lottery.pending_authority = new_authority;
+
+        Ok(())
+    }
+
+    /// Makes the proposed authority the lottery authority. Must be signed by it, which a
+    /// multisig vault does through its own transaction execution.
+    pub fn accept_authority(ctx: Context<AcceptAuthority>, _lottery_id: u64) -> Result<()> {
+        let token_lottery = &mut ctx.accounts.token_lottery;
+
+        require!(
+            token_lottery.pending_authority != Pubkey::default()
+                && ctx.accounts.payer.key() == token_lottery.pending_authority,
+            ErrorCode::Unauthorized
+        );
+
+        let previous_authority = token_lottery.authority;
+        token_lottery.authority = token_lottery.pending_authority;
+        token_lottery.pending_authority = Pubkey::default();
+
+        emit!(AuthorityTransferred {
+            lottery_id: token_lottery.lottery_id,
+            previous_authority,
+            new_authority: token_lottery.authority,
+        });
+
+        Ok(())
+    }
+
+    /// Runs a lottery that sold no tickets again with a new sales window.
+    pub fn reopen_lottery(
+        ctx: Context<ReopenLottery>,
+        _lottery_id: u64,
+        start_time: u64,
+        end_time: u64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let token_lottery = &mut ctx.accounts.token_lottery;
+
+        require!(
+            ctx.accounts.payer.key() == token_lottery.authority,
+            ErrorCode::Unauthorized
+        );
+        require!(start_time < end_time, ErrorCode::InvalidSalesWindow);
+
+        // A lottery that sold nothing is only stored as finished once cranked
+        token_lottery.sync_status(clock.slot);
+        token_lottery.settle_without_participants(clock.slot);
+        token_lottery.expect_status(
+            LotteryStatus::NoParticipants,
+            ErrorCode::LotteryHasParticipants,
+        )?;
+
+        token_lottery.start_time = start_time;
+        token_lottery.end_time = end_time;
+        token_lottery.randomness_account = Pubkey::default();
+        token_lottery.status = LotteryStatus::CollectionInitialized;
+
+        Ok(())
+    }
+
+    /// Closes a finished lottery (authority only): once it had no participants, every
+    /// tier is claimed, every ticket is refunded, or the claim period after `end_time` is
+    /// over. Whatever is left in the pot, unclaimed prizes included, and the rent of the
+    /// lottery accounts go to the configured `close_recipient`.
+    pub fn close_lottery<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CloseLottery<'info>>,
+        _lottery_id: u64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+
+        require!(
+            ctx.accounts.payer.key() == ctx.accounts.token_lottery.authority,
+            ErrorCode::Unauthorized
+        );
+        ctx.accounts.token_lottery.sync_status(clock.slot);
+        ctx.accounts.token_lottery.expect_closable(clock.slot)?;
+
+        let token_lottery = &ctx.accounts.token_lottery;
+        let recipient = ctx.accounts.recipient.to_account_info();
+
+        let token_payment = TokenPayment::for_lottery(
+            token_lottery,
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_ref(),
+            ctx.accounts.recipient_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            ctx.remaining_accounts,
+        )?;
+        if let Some(payment) = token_payment {
+            drain_vault(token_lottery, payment, &recipient)?;
+        }
+
+        // The collection NFT is burned so its token account can be closed; the mint and
+        // the Metaplex accounts stay, Metaplex only burns an empty sized collection
+        if let (
+            Some(collection_mint),
+            Some(collection_token_account),
+            Some(collection_token_bump),
+            Some(token_program),
+        ) = (
+            ctx.accounts.collection_mint.as_ref(),
+            ctx.accounts.collection_token_account.as_ref(),
+            ctx.bumps.collection_token_account,
+            ctx.accounts.token_program.as_ref(),
+        ) {
+            let lottery_id_bytes = token_lottery.lottery_id.to_le_bytes();
+            let signer_seeds: &[&[&[u8]]] = &[&[
+                b"collection_associated_token".as_ref(),
+                lottery_id_bytes.as_ref(),
+                &[collection_token_bump],
+            ]];
+
+            if collection_token_account.amount > 0 {
+                burn(
+                    CpiContext::new_with_signer(
+                        token_program.to_account_info(),
+                        Burn {
+                            mint: collection_mint.to_account_info(),
+                            from: collection_token_account.to_account_info(),
+                            authority: collection_token_account.to_account_info(),
+                        },
+                        signer_seeds,
+                    ),
+                    collection_token_account.amount,
+                )?;
+            }
+
+            close_account(CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                CloseAccount {
+                    account: collection_token_account.to_account_info(),
+                    destination: recipient,
+                    authority: collection_token_account.to_account_info(),
+                },
+                signer_seeds,
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Cancels a lottery before its winners are drawn, opening refunds for every ticket.
+    pub fn cancel_lottery(ctx: Context<CancelLottery>, _lottery_id: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        let token_lottery = &mut ctx.accounts.token_lottery;
+
+        require!(
+            ctx.accounts.payer.key() == token_lottery.authority,
+            ErrorCode::Unauthorized
+        );
+
+        // Once randomness is committed its outcome may already be known, so cancelling
+        // would let the authority veto an unwanted draw
+        token_lottery.sync_status(clock.slot);
+        require!(
+            matches!(
+                token_lottery.status,
+                LotteryStatus::Configured
+                    | LotteryStatus::CollectionInitialized
+                    | LotteryStatus::Open
+                    | LotteryStatus::Closed
+            ),
+            ErrorCode::LotteryNotCancellable
+        );
+
+        token_lottery.status = LotteryStatus::Cancelled;
+
+        Ok(())
+    }
+
+    /// Burns a ticket NFT of a cancelled or undersubscribed lottery and refunds the pot
+    /// share of every ticket in its range to the holder, closing its ticket record.
+    pub fn claim_refund<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimRefund<'info>>,
+        _lottery_id: u64,
+        first_ticket: u64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        ctx.accounts.token_lottery.begin_refund(clock.slot)?;
+
+        require!(
+            ctx.accounts.ticket_account.amount > 0,
+            ErrorCode::IncorrectTicket
+        );
+
+        burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.ticket_mint.to_account_info(),
+                    from: ctx.accounts.ticket_account.to_account_info(),
+                    authority: ctx.accounts.payer.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+
+        let token_payment = TokenPayment::for_lottery(
+            &ctx.accounts.token_lottery,
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_ref(),
+            ctx.accounts.refund_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            ctx.remaining_accounts,
+        )?;
+
+        pay_refund(
+            &mut ctx.accounts.token_lottery,
+            &ctx.accounts.payer.to_account_info(),
+            token_payment,
+            first_ticket,
+            ctx.accounts.ticket_record.quantity,
+        )
+    }
+
+    /// Refunds a ticket of a cancelled or undersubscribed `Ledger` lottery to its registry
+    /// entry, clearing the entry so it can't be refunded twice.
+    pub fn claim_ledger_refund<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimLedgerRefund<'info>>,
+        _lottery_id: u64,
+        ticket_index: u64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        ctx.accounts.token_lottery.begin_refund(clock.slot)?;
+
+        {
+            let mut registry_data = ctx
+                .accounts
+                .ticket_registry
+                .as_ref()
+                .try_borrow_mut_data()?;
+            require!(
+                read_entry(&registry_data, ticket_index)? == ctx.accounts.payer.key(),
+                ErrorCode::IncorrectTicket
+            );
+            clear_entry(&mut registry_data, ticket_index)?;
+        }
+
+        let token_payment = TokenPayment::for_lottery(
+            &ctx.accounts.token_lottery,
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_ref(),
+            ctx.accounts.refund_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            ctx.remaining_accounts,
+        )?;
+
+        pay_refund(
+            &mut ctx.accounts.token_lottery,
+            &ctx.accounts.payer.to_account_info(),
+            token_payment,
+            ticket_index,
+            1,
+        )
+    }
+
+    /// Burns a compressed ticket of a cancelled or undersubscribed lottery and refunds its
+    /// pot share to the holder. Bubblegum checks `proof` and the payer's ownership of the
+    /// leaf while burning it.
+    pub fn claim_compressed_refund<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimCompressedRefund<'info>>,
+        _lottery_id: u64,
+        ticket_index: u64,
+        proof: TicketLeafProof,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        ctx.accounts.token_lottery.begin_refund(clock.slot)?;
+
+        let (proof_accounts, hook_accounts) =
+            proof.split_remaining_accounts(ctx.remaining_accounts)?;
+        let proof_nodes: Vec<_> = proof_accounts
+            .iter()
+            .map(|node| (node, false, false))
+            .collect();
+
+        BurnCpiBuilder::new(&ctx.accounts.bubblegum_program.to_account_info())
+            .tree_config(&ctx.accounts.tree_config.to_account_info())
+            .leaf_owner(&ctx.accounts.payer.to_account_info(), true)
+            .leaf_delegate(&ctx.accounts.leaf_delegate.to_account_info(), false)
+            .merkle_tree(&ctx.accounts.merkle_tree.to_account_info())
+            .log_wrapper(&ctx.accounts.log_wrapper.to_account_info())
+            .compression_program(&ctx.accounts.compression_program.to_account_info())
+            .system_program(&ctx.accounts.system_program.to_account_info())
+            .root(proof.root)
+            .data_hash(proof.data_hash)
+            .creator_hash(proof.creator_hash)
+            .nonce(ticket_index)
+            .index(leaf_index(ticket_index)?)
+            .add_remaining_accounts(&proof_nodes)
+            .invoke()?;
+
+        let token_payment = TokenPayment::for_lottery(
+            &ctx.accounts.token_lottery,
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_ref(),
+            ctx.accounts.refund_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            hook_accounts,
+        )?;
+
+        pay_refund(
+            &mut ctx.accounts.token_lottery,
+            &ctx.accounts.payer.to_account_info(),
+            token_payment,
+            ticket_index,
+            1,
+        )
+    }
+
+    /// Refunds the tickets of a Core asset of a cancelled or undersubscribed lottery. The
+    /// asset is burned, so its range can't be refunded twice.
+    pub fn claim_core_refund<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimCoreRefund<'info>>,
+        _lottery_id: u64,
+        _first_ticket: u64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        ctx.accounts.token_lottery.begin_refund(clock.slot)?;
+
+        let tickets = verify_ticket_asset(
+            &ctx.accounts.ticket_asset.to_account_info(),
+            &ctx.accounts.core_collection.key(),
+            &ctx.accounts.payer.key(),
+        )?;
+
+        BurnV1CpiBuilder::new(&ctx.accounts.mpl_core_program.to_account_info())
+            .asset(&ctx.accounts.ticket_asset.to_account_info())
+            .collection(Some(&ctx.accounts.core_collection.to_account_info()))
+            .payer(&ctx.accounts.payer.to_account_info())
+            .system_program(Some(&ctx.accounts.system_program.to_account_info()))
+            .invoke()?;
+
+        let token_payment = TokenPayment::for_lottery(
+            &ctx.accounts.token_lottery,
+            ctx.accounts.payment_mint.as_ref(),
+            ctx.accounts.vault.as_ref(),
+            ctx.accounts.refund_token_account.as_ref(),
+            ctx.accounts.payment_token_program.as_ref(),
+            ctx.remaining_accounts,
+        )?;
+
+        pay_refund(
+            &mut ctx.accounts.token_lottery,
+            &ctx.accounts.payer.to_account_info(),
+            token_payment,
+            tickets.start,
+            tickets.end - tickets.start,
+        )
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + TokenLottery::INIT_SPACE,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    /// Only provided for lotteries paid in an SPL token instead of SOL
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = payer,
+        token::mint = payment_mint,
+        token::authority = token_lottery,
+        token::token_program = payment_token_program,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct InitializeLottery<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
     pub token_lottery: Account<'info, TokenLottery>,
 
     #[account(
         init,
         payer = payer,
-        seeds = [token_lottery.total_tickets.to_le_bytes().as_ref()],
-        bump,
         mint::decimals = 0,
         mint::authority = collection_mint,
         mint::freeze_authority = collection_mint,
-        mint::token_program = token_program,
+        seeds = [b"collection_mint".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump
     )]
-    pub ticket_mint: InterfaceAccount<'info, Mint>,
+    pub collection_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        token::mint = collection_mint,
+        token::authority = collection_token_account,
+        seeds = [b"collection_associated_token".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub collection_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), collection_mint.key().as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    /// CHECK: Checked by the metadata smart contract
+    pub metadata: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), collection_mint.key().as_ref(), b"edition".as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    /// CHECK: Checked by the metadata smart contract
+    pub master_edition: UncheckedAccount<'info>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct BuyTicket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [lottery_id.to_le_bytes().as_ref(), token_lottery.total_tickets.to_le_bytes().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = collection_mint,
+        mint::freeze_authority = collection_mint,
+        mint::token_program = token_program,
+    )]
+    pub ticket_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + TicketRecord::INIT_SPACE,
+        seeds = [b"ticket_record".as_ref(), lottery_id.to_le_bytes().as_ref(), token_lottery.total_tickets.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub ticket_record: Account<'info, TicketRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), ticket_mint.key().as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key()
+    )]
+    /// CHECK: Checked by the metadata smart contract
+    pub ticket_metadata: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), ticket_mint.key().as_ref(), b"edition".as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    /// CHECK: Checked by the metadata smart contract
+    pub ticket_master_edition: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), collection_mint.key().as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    /// CHECK: Checked by the metadata smart contract
+    pub collection_metadata: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), collection_mint.key().as_ref(), b"edition".as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    /// CHECK: Checked by the metadata smart contract
+    pub collection_master_edition: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"collection_mint".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub collection_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = ticket_mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program,
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub payer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct InitializeToken2022Collection<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(
+        init,
+        payer = payer,
+        mint::decimals = 0,
+        mint::authority = collection_mint,
+        mint::freeze_authority = collection_mint,
+        mint::token_program = token_program,
+        extensions::metadata_pointer::authority = collection_mint,
+        extensions::metadata_pointer::metadata_address = collection_mint,
+        extensions::group_pointer::authority = collection_mint,
+        extensions::group_pointer::group_address = collection_mint,
+        seeds = [b"collection_mint".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub collection_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct BuyToken2022Tickets<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [lottery_id.to_le_bytes().as_ref(), token_lottery.total_tickets.to_le_bytes().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = collection_mint,
+        mint::freeze_authority = collection_mint,
+        mint::token_program = token_program,
+        extensions::metadata_pointer::authority = collection_mint,
+        extensions::metadata_pointer::metadata_address = ticket_mint,
+        extensions::group_member_pointer::authority = collection_mint,
+        extensions::group_member_pointer::member_address = ticket_mint,
+    )]
+    pub ticket_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + TicketRecord::INIT_SPACE,
+        seeds = [b"ticket_record".as_ref(), lottery_id.to_le_bytes().as_ref(), token_lottery.total_tickets.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub ticket_record: Account<'info, TicketRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"collection_mint".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub collection_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = ticket_mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program,
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub payer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct InitializeCoreCollection<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(
+        mut,
+        seeds = [b"core_collection".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    /// CHECK: Created and checked by the MPL Core program
+    pub core_collection: UncheckedAccount<'info>,
+
+    #[account(address = mpl_core::ID)]
+    /// CHECK: MPL Core program
+    pub mpl_core_program: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct BuyCoreTickets<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(
+        mut,
+        seeds = [lottery_id.to_le_bytes().as_ref(), token_lottery.total_tickets.to_le_bytes().as_ref()],
+        bump,
+    )]
+    /// CHECK: Created by the MPL Core program
+    pub ticket_asset: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"core_collection".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    /// CHECK: Created and checked by the MPL Core program
+    pub core_collection: UncheckedAccount<'info>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub payer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+
+    #[account(address = mpl_core::ID)]
+    /// CHECK: MPL Core program
+    pub mpl_core_program: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct InitializeTicketTree<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(
+        mut,
+        seeds = [merkle_tree.key().as_ref()],
+        bump,
+        seeds::program = bubblegum_program.key(),
+    )]
+    /// CHECK: Checked by the Bubblegum program
+    pub tree_config: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    /// CHECK: Initialized by the account compression program
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    #[account(address = mpl_bubblegum::ID)]
+    /// CHECK: Bubblegum program
+    pub bubblegum_program: UncheckedAccount<'info>,
+
+    #[account(address = SPL_NOOP_ID)]
+    /// CHECK: SPL noop program
+    pub log_wrapper: UncheckedAccount<'info>,
+
+    #[account(address = SPL_ACCOUNT_COMPRESSION_ID)]
+    /// CHECK: SPL account compression program
+    pub compression_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct BuyCompressedTicket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(mut, address = token_lottery.merkle_tree)]
+    /// CHECK: Checked by the account compression program
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [merkle_tree.key().as_ref()],
+        bump,
+        seeds::program = bubblegum_program.key(),
+    )]
+    /// CHECK: Checked by the Bubblegum program
+    pub tree_config: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"collection_mint".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub collection_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), collection_mint.key().as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    /// CHECK: Checked by the metadata smart contract
+    pub collection_metadata: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), collection_mint.key().as_ref(), b"edition".as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    /// CHECK: Checked by the metadata smart contract
+    pub collection_master_edition: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"collection_cpi".as_ref()],
+        bump,
+        seeds::program = bubblegum_program.key(),
+    )]
+    /// CHECK: Bubblegum's signer for collection CPIs
+    pub bubblegum_signer: UncheckedAccount<'info>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub payer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+
+    #[account(address = mpl_bubblegum::ID)]
+    /// CHECK: Bubblegum program
+    pub bubblegum_program: UncheckedAccount<'info>,
+
+    #[account(address = SPL_NOOP_ID)]
+    /// CHECK: SPL noop program
+    pub log_wrapper: UncheckedAccount<'info>,
+
+    #[account(address = SPL_ACCOUNT_COMPRESSION_ID)]
+    /// CHECK: SPL account compression program
+    pub compression_program: UncheckedAccount<'info>,
+
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct InitializeTicketRegistry<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = TicketRegistry::space(0),
+        seeds = [b"ticket_registry".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub ticket_registry: AccountLoader<'info, TicketRegistry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, quantity: u64)]
+pub struct BuyLedgerTickets<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(
+        mut,
+        seeds = [b"ticket_registry".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+        realloc = TicketRegistry::space(token_lottery.total_tickets.saturating_add(quantity)),
+        realloc::payer = payer,
+        realloc::zero = false,
+    )]
+    pub ticket_registry: AccountLoader<'info, TicketRegistry>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub payer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct CommitRandomness<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    /// CHECK: Checked by the lottery's `RandomnessSource`
+    pub randomness_account: UncheckedAccount<'info>,
+
+    /// CHECK: The randomness being replaced on a recommit, checked by the `RandomnessSource`
+    #[account(address = token_lottery.randomness_account)]
+    pub stale_randomness_account: Option<UncheckedAccount<'info>>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Receives the crank bounty of SPL lotteries
+    #[account(mut)]
+    pub payer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct RevealWinner<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    /// CHECK: Checked by the lottery's `RandomnessSource`
+    pub randomness_account: UncheckedAccount<'info>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Receives the crank bounty of SPL lotteries
+    #[account(mut)]
+    pub payer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct RevealSecret<'info> {
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    /// CHECK: The `SlotHashes` sysvar recorded at commit, checked by the `RandomnessSource`
+    #[account(address = token_lottery.randomness_account)]
+    pub randomness_account: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, tier: u8, first_ticket: u64)]
+pub struct ClaimWinnings<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+        constraint = (tier as usize) < token_lottery.winners.len() @ ErrorCode::InvalidPrizeTier,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(
+        seeds = [b"ticket_record".as_ref(), lottery_id.to_le_bytes().as_ref(), first_ticket.to_le_bytes().as_ref()],
+        bump,
+        constraint = ticket_record.lottery == token_lottery.key() @ ErrorCode::IncorrectTicket,
+        constraint = ticket_record.mint == ticket_mint.key() @ ErrorCode::IncorrectTicket,
+        constraint = ticket_record.contains(token_lottery.winners[tier as usize]) @ ErrorCode::IncorrectTicket,
+    )]
+    pub ticket_record: Account<'info, TicketRecord>,
+
+    #[account(
+        seeds = [lottery_id.to_le_bytes().as_ref(), first_ticket.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub ticket_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        associated_token::mint = ticket_mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program,
+    )]
+    pub ticket_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub winner_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, tier: u8)]
+pub struct ClaimCompressedWinnings<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+        constraint = (tier as usize) < token_lottery.winners.len() @ ErrorCode::InvalidPrizeTier,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    /// CHECK: Only hashed into the ticket leaf
+    pub leaf_delegate: UncheckedAccount<'info>,
+
+    #[account(address = token_lottery.merkle_tree)]
+    /// CHECK: Checked by the account compression program
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    #[account(address = SPL_ACCOUNT_COMPRESSION_ID)]
+    /// CHECK: SPL account compression program
+    pub compression_program: UncheckedAccount<'info>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub winner_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, tier: u8)]
+pub struct ClaimLedgerWinnings<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+        constraint = (tier as usize) < token_lottery.winners.len() @ ErrorCode::InvalidPrizeTier,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(
+        seeds = [b"ticket_registry".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub ticket_registry: AccountLoader<'info, TicketRegistry>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub winner_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, tier: u8, first_ticket: u64)]
+pub struct ClaimCoreWinnings<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+        constraint = (tier as usize) < token_lottery.winners.len() @ ErrorCode::InvalidPrizeTier,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(
+        seeds = [lottery_id.to_le_bytes().as_ref(), first_ticket.to_le_bytes().as_ref()],
+        bump,
+    )]
+    /// CHECK: Checked in verify_ticket_asset
+    pub ticket_asset: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"core_collection".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    /// CHECK: Created and checked by the MPL Core program
+    pub core_collection: UncheckedAccount<'info>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub winner_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, first_ticket: u64)]
+pub struct MarkWinnerMetadata<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(
+        seeds = [b"ticket_record".as_ref(), lottery_id.to_le_bytes().as_ref(), first_ticket.to_le_bytes().as_ref()],
+        bump,
+        constraint = ticket_record.lottery == token_lottery.key() @ ErrorCode::IncorrectTicket,
+        constraint = ticket_record.mint == ticket_mint.key() @ ErrorCode::IncorrectTicket,
+    )]
+    pub ticket_record: Account<'info, TicketRecord>,
+
+    #[account(
+        mut,
+        seeds = [lottery_id.to_le_bytes().as_ref(), first_ticket.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub ticket_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"collection_mint".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub collection_mint: InterfaceAccount<'info, Mint>,
+
+    /// Metaplex metadata of the ticket, only for `Nft` lotteries
+    #[account(
+        mut,
+        seeds = [b"metadata", mpl_token_metadata::ID.as_ref(), ticket_mint.key().as_ref()],
+        bump,
+        seeds::program = mpl_token_metadata::ID,
+    )]
+    pub ticket_metadata: Option<Account<'info, MetadataAccount>>,
+
+    pub token_metadata_program: Option<Program<'info, Metadata>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, first_ticket: u64)]
+pub struct BurnTicket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: The lottery PDA, read as a `TokenLottery` unless `close_lottery` closed it
+    #[account(
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub token_lottery: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = payer,
+        seeds = [b"ticket_record".as_ref(), lottery_id.to_le_bytes().as_ref(), first_ticket.to_le_bytes().as_ref()],
+        bump,
+        constraint = ticket_record.lottery == token_lottery.key() @ ErrorCode::IncorrectTicket,
+        constraint = ticket_record.mint == ticket_mint.key() @ ErrorCode::IncorrectTicket,
+    )]
+    pub ticket_record: Account<'info, TicketRecord>,
+
+    #[account(
+        mut,
+        seeds = [lottery_id.to_le_bytes().as_ref(), first_ticket.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub ticket_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = ticket_mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program,
+    )]
+    pub ticket_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), ticket_mint.key().as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    /// CHECK: Checked by the metadata smart contract
+    pub ticket_metadata: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), ticket_mint.key().as_ref(), b"edition".as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    /// CHECK: Checked by the metadata smart contract
+    pub ticket_master_edition: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"collection_mint".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub collection_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), collection_mint.key().as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    /// CHECK: Checked by the metadata smart contract
+    pub collection_metadata: UncheckedAccount<'info>,
+
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct ProposeAuthority<'info> {
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct AcceptAuthority<'info> {
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct ReopenLottery<'info> {
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct CloseLottery<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        close = recipient,
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(mut, address = token_lottery.close_recipient)]
+    /// CHECK: Only receives lamports
+    pub recipient: UncheckedAccount<'info>,
+
+    /// Only for `Ledger` lotteries
+    #[account(
+        mut,
+        close = recipient,
+        seeds = [b"ticket_registry".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub ticket_registry: Option<AccountLoader<'info, TicketRegistry>>,
+
+    /// Only for lotteries with a Metaplex collection, along with `collection_token_account`
+    #[account(
+        mut,
+        seeds = [b"collection_mint".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub collection_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"collection_associated_token".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub collection_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Writable so transfer fees withheld in the vault can be harvested to it
+    #[account(mut, address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
 
     #[account(
         mut,
-        seeds = [b"metadata", token_metadata_program.key().as_ref(), ticket_mint.key().as_ref()],
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
         bump,
-        seeds::program = token_metadata_program.key()
     )]
-    /// CHECK: Checked by the metadata smart contract
-    pub ticket_metadata: UncheckedAccount<'info>,
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         mut,
-        seeds = [b"metadata", token_metadata_program.key().as_ref(), ticket_mint.key().as_ref(), b"edition".as_ref()],
-        bump,
-        seeds::program = token_metadata_program.key(),
+        token::authority = recipient,
     )]
-    /// CHECK: Checked by the metadata smart contract
-    pub ticket_master_edition: UncheckedAccount<'info>,
+    pub recipient_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct CancelLottery<'info> {
+    pub payer: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [b"metadata", token_metadata_program.key().as_ref(), collection_mint.key().as_ref()],
-        bump,
-        seeds::program = token_metadata_program.key(),
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
     )]
-    /// CHECK: Checked by the metadata smart contract
-    pub collection_metadata: UncheckedAccount<'info>,
+    pub token_lottery: Account<'info, TokenLottery>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, first_ticket: u64)]
+pub struct ClaimRefund<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [b"metadata", token_metadata_program.key().as_ref(), collection_mint.key().as_ref(), b"edition".as_ref()],
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump = token_lottery.bump,
+    )]
+    pub token_lottery: Account<'info, TokenLottery>,
+
+    #[account(
+        mut,
+        close = payer,
+        seeds = [b"ticket_record".as_ref(), lottery_id.to_le_bytes().as_ref(), first_ticket.to_le_bytes().as_ref()],
         bump,
-        seeds::program = token_metadata_program.key(),
+        constraint = ticket_record.lottery == token_lottery.key() @ ErrorCode::IncorrectTicket,
+        constraint = ticket_record.mint == ticket_mint.key() @ ErrorCode::IncorrectTicket,
     )]
-    /// CHECK: Checked by the metadata smart contract
-    pub collection_master_edition: UncheckedAccount<'info>,
+    pub ticket_record: Account<'info, TicketRecord>,
 
     #[account(
         mut,
-        seeds = [b"collection_mint".as_ref()],
-        bump
+        seeds = [lottery_id.to_le_bytes().as_ref(), first_ticket.to_le_bytes().as_ref()],
+        bump,
     )]
-    pub collection_mint: InterfaceAccount<'info, Mint>,
+    pub ticket_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
-        init,
-        payer = payer,
+        mut,
         associated_token::mint = ticket_mint,
         associated_token::authority = payer,
         associated_token::token_program = token_program,
     )]
-    pub destination: InterfaceAccount<'info, TokenAccount>,
+    pub ticket_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub refund_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_metadata_program: Program<'info, Metadata>,
     pub token_program: Interface<'info, TokenInterface>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
 }
 
 #[derive(Accounts)]
-pub struct CommitRandomness<'info> {
+#[instruction(lottery_id: u64)]
+pub struct ClaimLedgerRefund<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [b"token_lottery".as_ref()],
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
         bump = token_lottery.bump,
     )]
     pub token_lottery: Account<'info, TokenLottery>,
 
-    /// CHECK: Checked by the Switchboardsmart contract
-    pub randomness_account: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"ticket_registry".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub ticket_registry: AccountLoader<'info, TicketRegistry>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub refund_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
 }
 
 #[derive(Accounts)]
-pub struct RevealWinner<'info> {
+#[instruction(lottery_id: u64)]
+pub struct ClaimCompressedRefund<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [b"token_lottery".as_ref()],
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
         bump = token_lottery.bump,
     )]
     pub token_lottery: Account<'info, TokenLottery>,
 
-    /// CHECK: Checked by the Switchboardsmart contract
-    pub randomness_account: UncheckedAccount<'info>,
+    /// CHECK: Checked by the Bubblegum program as part of the ticket leaf
+    pub leaf_delegate: UncheckedAccount<'info>,
+
+    #[account(mut, address = token_lottery.merkle_tree)]
+    /// CHECK: Checked by the account compression program
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [merkle_tree.key().as_ref()],
+        bump,
+        seeds::program = bubblegum_program.key(),
+    )]
+    /// CHECK: Checked by the Bubblegum program
+    pub tree_config: UncheckedAccount<'info>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub refund_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+
+    #[account(address = mpl_bubblegum::ID)]
+    /// CHECK: Bubblegum program
+    pub bubblegum_program: UncheckedAccount<'info>,
+
+    #[account(address = SPL_NOOP_ID)]
+    /// CHECK: SPL noop program
+    pub log_wrapper: UncheckedAccount<'info>,
+
+    #[account(address = SPL_ACCOUNT_COMPRESSION_ID)]
+    /// CHECK: SPL account compression program
+    pub compression_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct ClaimWinnings<'info> {
+#[instruction(lottery_id: u64, first_ticket: u64)]
+pub struct ClaimCoreRefund<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [b"token_lottery".as_ref()],
+        seeds = [b"token_lottery".as_ref(), lottery_id.to_le_bytes().as_ref()],
         bump = token_lottery.bump,
     )]
     pub token_lottery: Account<'info, TokenLottery>,
 
     #[account(
-        seeds = [token_lottery.winner.to_le_bytes().as_ref()],
+        mut,
+        seeds = [lottery_id.to_le_bytes().as_ref(), first_ticket.to_le_bytes().as_ref()],
         bump,
     )]
-    pub ticket_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Checked in verify_ticket_asset
+    pub ticket_asset: UncheckedAccount<'info>,
 
     #[account(
-        seeds = [b"collection_mint".as_ref()],
+        mut,
+        seeds = [b"core_collection".as_ref(), lottery_id.to_le_bytes().as_ref()],
         bump,
     )]
-    pub collection_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Created and checked by the MPL Core program
+    pub core_collection: UncheckedAccount<'info>,
+
+    #[account(address = token_lottery.payment_mint)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
 
     #[account(
-        seeds = [b"metadata", token_metadata_program.key().as_ref(), ticket_mint.key().as_ref()],
+        mut,
+        seeds = [b"vault".as_ref(), lottery_id.to_le_bytes().as_ref()],
         bump,
-        seeds::program = token_metadata_program.key(),
     )]
-    pub ticket_metadata: Account<'info, MetadataAccount>,
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
 
-    #[account(
-        associated_token::mint = ticket_mint,
-        associated_token::authority = payer,
-        associated_token::token_program = token_program,
-    )]
-    pub ticket_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub refund_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
-    #[account(
-        seeds = [b"metadata", token_metadata_program.key().as_ref(), collection_mint.key().as_ref()],
-        bump,
-        seeds::program = token_metadata_program.key(),
-    )]
-    pub collection_metadata: Account<'info, MetadataAccount>,
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
 
-    pub token_metadata_program: Program<'info, Metadata>,
-    pub token_program: Interface<'info, TokenInterface>,
+    #[account(address = mpl_core::ID)]
+    /// CHECK: MPL Core program
+    pub mpl_core_program: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[account]
 #[derive(InitSpace)]
 pub struct TokenLottery {
     pub bump: u8,
-    pub winner: u64,
-    pub winner_chosen: bool,
+    pub lottery_id: u64,
+    /// Winning ticket index for each filled prize tier, in tier order
+    #[max_len(MAX_PRIZE_TIERS)]
+    pub winners: Vec<u64>,
+    pub status: LotteryStatus,
     pub start_time: u64,
     pub end_time: u64,
     pub lottery_pot_amount: u64,
@@ -624,6 +3118,540 @@ pub struct TokenLottery {
     pub ticket_price: u64,
     pub authority: Pubkey,
     pub randomness_account: Pubkey,
+    /// SPL mint tickets are paid in, `Pubkey::default()` for SOL
+    pub payment_mint: Pubkey,
+    /// Share of the prize pool for each tier, in basis points
+    #[max_len(MAX_PRIZE_TIERS)]
+    pub prize_tiers: Vec<u16>,
+    /// Pot snapshot taken when the winners are revealed, split across the tiers
+    pub prize_pool: u64,
+    /// Bit `i` is set once tier `i` has been paid
+    pub claimed_tiers: u8,
+    /// Tickets that must be sold for the draw to happen, otherwise tickets are refunded
+    pub min_tickets: u64,
+    pub tickets_refunded: u64,
+    pub ticket_mode: TicketMode,
+    /// Bubblegum tree compressed tickets are minted into, `Pubkey::default()` until created
+    pub merkle_tree: Pubkey,
+    /// Collection and ticket metadata, empty until the collection is created
+    pub metadata: CollectionMetadata,
+    /// Slots after the draw winners have to claim, after which `close_lottery` sweeps
+    /// unclaimed prizes
+    pub claim_period: u64,
+    /// Receives the remaining pot and the rent of the lottery accounts on `close_lottery`
+    pub close_recipient: Pubkey,
+    /// Authority proposed by `propose_authority`, `Pubkey::default()` when none is pending
+    pub pending_authority: Pubkey,
+    /// Share of the pot, in basis points, paid to whoever calls `commit_randomness` and
+    /// `reveal_winner`
+    pub crank_bounty_bps: u16,
+    /// Slots after a commit past which unresolved randomness can be replaced
+    pub reveal_timeout: u64,
+    /// Slot the current randomness was committed in, one after its seed slot
+    pub randomness_commit_slot: u64,
+    /// Slot the winners were drawn in, from which `claim_period` runs
+    pub reveal_slot: u64,
+    /// Provider of the winning randomness, chosen per lottery
+    pub randomness_source: RandomnessSource,
+}
+
+/// Authoritative record of the consecutive tickets `index..index + quantity` issued as
+/// the NFT `mint`, written when the tickets are bought.
+#[account]
+#[derive(InitSpace)]
+pub struct TicketRecord {
+    pub lottery: Pubkey,
+    /// First ticket number of the purchase
+    pub index: u64,
+    pub quantity: u64,
+    pub mint: Pubkey,
+}
+
+impl TicketRecord {
+    pub fn contains(&self, ticket: u64) -> bool {
+        ticket >= self.index && ticket - self.index < self.quantity
+    }
+}
+
+/// How tickets are issued to buyers.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum TicketMode {
+    /// A Metaplex NFT per purchase, with its own mint, token account, metadata and edition
+    Nft,
+    /// A Bubblegum compressed NFT per ticket, minted into the lottery's Merkle tree
+    Compressed,
+    /// The buyer's pubkey recorded in the lottery's `TicketRegistry`, no token at all
+    Ledger,
+    /// A Token-2022 NFT per purchase, with token metadata and group member extensions
+    /// instead of Metaplex accounts
+    Token2022,
+    /// An MPL Core asset per purchase in the lottery's Core collection, the range recorded
+    /// in its attributes plugin
+    Core,
+}
+
+/// Lifecycle of a lottery. Time-driven transitions (opening and closing sales) are
+/// applied lazily by [`TokenLottery::sync_status`] at the start of each instruction.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum LotteryStatus {
+    /// `initialize_config` ran, the ticket collection does not exist yet
+    Configured,
+    /// The ticket collection (or ticket registry) exists, sales have not started
+    CollectionInitialized,
+    /// Tickets can be bought
+    Open,
+    /// Sales ended with enough tickets sold, waiting for randomness
+    Closed,
+    RandomnessCommitted,
+    /// Winners drawn, prizes can be claimed
+    WinnerRevealed,
+    /// Every prize tier has been paid
+    Claimed,
+    /// Cancelled by the authority, tickets can be refunded
+    Cancelled,
+    /// Refunds in progress, after a cancellation or an undersubscribed sale
+    Refunding,
+    /// Sales ended without any ticket sold
+    NoParticipants,
+}
+
+impl TokenLottery {
+    pub fn pays_in_sol(&self) -> bool {
+        self.payment_mint == Pubkey::default()
+    }
+
+    /// Adds a ticket payment to the prize pot.
+    pub fn credit_pot(&mut self, amount: u64) -> Result<()> {
+        self.lottery_pot_amount = self
+            .lottery_pot_amount
+            .checked_add(amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Removes a payout from the prize pot.
+    pub fn debit_pot(&mut self, amount: u64) -> Result<()> {
+        self.lottery_pot_amount = self
+            .lottery_pot_amount
+            .checked_sub(amount)
+            .ok_or(ErrorCode::InsufficientPot)?;
+        Ok(())
+    }
+
+    /// Applies the transitions driven by the clock: sales open at `start_time` and close
+    /// at `end_time`, going to refunds if fewer than `min_tickets` were sold.
+    pub fn sync_status(&mut self, slot: u64) {
+        if self.status == LotteryStatus::CollectionInitialized && slot >= self.start_time {
+            self.status = LotteryStatus::Open;
+        }
+
+        if self.status == LotteryStatus::Open && slot >= self.end_time {
+            self.status = if self.total_tickets > 0 && self.total_tickets < self.min_tickets {
+                LotteryStatus::Refunding
+            } else {
+                LotteryStatus::Closed
+            };
+        }
+
+        // Only the secret holder can reveal a commit-reveal draw; withholding the secret
+        // past the deadline refunds the tickets instead of locking the pot
+        if self.status == LotteryStatus::RandomnessCommitted
+            && self.randomness_source.refunds_on_timeout()
+            && slot >= self.reveal_deadline()
+        {
+            self.status = LotteryStatus::Refunding;
+        }
+    }
+
+    /// Applies the clock and checks tickets can still be sold. Sales never overlap a
+    /// randomness commitment, so no buyer can act on an outcome that may be known.
+    pub fn expect_sales_open(&mut self, slot: u64) -> Result<()> {
+        self.sync_status(slot);
+        self.expect_status(LotteryStatus::Open, ErrorCode::LotteryNotOpen)?;
+        // Unreachable through `commit_randomness`, which needs sales closed; this only
+        // guards against `reopen_lottery` reopening sales with randomness still recorded
+        require!(
+            self.randomness_account == Pubkey::default(),
+            ErrorCode::RandomnessAlreadyCommitted
+        );
+        Ok(())
+    }
+
+    /// Checks ticket sales are over, so randomness committed now can't be watched while
+    /// tickets are still bought.
+    pub fn expect_sales_closed(&self, slot: u64) -> Result<()> {
+        require!(slot >= self.end_time, ErrorCode::LotteryNotCompleted);
+        self.expect_status(LotteryStatus::Closed, ErrorCode::LotteryNotCompleted)
+    }
+
+    /// Finalizes a closed lottery that sold no tickets without drawing, so the authority
+    /// can reopen or close it. Returns whether it did.
+    pub fn settle_without_participants(&mut self, slot: u64) -> bool {
+        if self.status != LotteryStatus::Closed || self.total_tickets > 0 {
+            return false;
+        }
+
+        self.status = LotteryStatus::NoParticipants;
+        emit!(LotteryHadNoParticipants {
+            lottery_id: self.lottery_id,
+            slot,
+        });
+        true
+    }
+
+    /// Checks the randomness is committed and the winners not drawn yet.
+    pub fn expect_randomness_committed(&self) -> Result<()> {
+        require!(
+            !matches!(
+                self.status,
+                LotteryStatus::WinnerRevealed | LotteryStatus::Claimed
+            ),
+            ErrorCode::WinnerChosen
+        );
+        self.expect_status(
+            LotteryStatus::RandomnessCommitted,
+            ErrorCode::RandomnessNotCommitted,
+        )
+    }
+
+    /// Draws the winners from `randomness` at `slot` and snapshots the pot as the prize
+    /// pool.
+    pub fn record_draw(&mut self, randomness: &[u8; 32], slot: u64) {
+        // Tiers beyond the number of tickets sold stay empty and their share is spread
+        // over the filled tiers
+        let winner_count = (self.prize_tiers.len() as u64).min(self.total_tickets) as usize;
+        self.winners = draw_winners(randomness, self.total_tickets, winner_count);
+        self.prize_pool = self.lottery_pot_amount;
+        self.reveal_slot = slot;
+        self.status = LotteryStatus::WinnerRevealed;
+    }
+
+    /// Fails with `error` unless the lottery is in `expected` status.
+    pub fn expect_status(&self, expected: LotteryStatus, error: ErrorCode) -> Result<()> {
+        if self.status != expected {
+            msg!("Lottery is {:?}, expected {:?}", self.status, expected);
+            return Err(error.into());
+        }
+        Ok(())
+    }
+
+    /// Checks that nothing is left to pay out: no ticket was sold, every tier is claimed,
+    /// every ticket is refunded, or the claim period is over.
+    pub fn expect_closable(&self, slot: u64) -> Result<()> {
+        let closable = match self.status {
+            LotteryStatus::NoParticipants | LotteryStatus::Claimed => true,
+            LotteryStatus::Closed => self.total_tickets == 0,
+            LotteryStatus::Cancelled | LotteryStatus::Refunding => {
+                self.tickets_refunded == self.total_tickets
+            }
+            LotteryStatus::WinnerRevealed => {
+                slot >= self.reveal_slot.saturating_add(self.claim_period)
+            }
+            _ => false,
+        };
+        require!(closable, ErrorCode::LotteryNotFinished);
+        Ok(())
+    }
+
+    /// Moves a cancelled or undersubscribed lottery into refunds.
+    pub fn begin_refund(&mut self, slot: u64) -> Result<()> {
+        self.sync_status(slot);
+        require!(
+            matches!(
+                self.status,
+                LotteryStatus::Cancelled | LotteryStatus::Refunding
+            ),
+            ErrorCode::RefundsNotAvailable
+        );
+        self.status = LotteryStatus::Refunding;
+        Ok(())
+    }
+
+    /// Pot share returned for `quantity` refunded tickets: the pot split evenly over the
+    /// tickets not yet refunded. For SOL lotteries this is exactly `ticket_price` per
+    /// ticket; for mints with a transfer fee it is what the pot actually received.
+    pub fn refund_share(&self, quantity: u64) -> Result<u64> {
+        let outstanding_tickets = self
+            .total_tickets
+            .checked_sub(self.tickets_refunded)
+            .filter(|tickets| *tickets >= quantity && *tickets > 0)
+            .ok_or(ErrorCode::RefundsNotAvailable)?;
+
+        let share = (self.lottery_pot_amount as u128)
+            .checked_mul(quantity as u128)
+            .ok_or(ErrorCode::ArithmeticOverflow)?
+            / outstanding_tickets as u128;
+
+        Ok(share as u64)
+    }
+
+    /// First slot at which the committed randomness can be replaced if still unrevealed, or
+    /// at which a commit-reveal lottery moves to refunds.
+    pub fn reveal_deadline(&self) -> u64 {
+        self.randomness_commit_slot
+            .saturating_add(self.reveal_timeout)
+    }
+
+    /// Bounty paid for the next crank: `crank_bounty_bps` of what is left in the pot.
+    pub fn crank_reward(&self) -> u64 {
+        (self.lottery_pot_amount as u128 * self.crank_bounty_bps as u128 / BASIS_POINTS as u128)
+            as u64
+    }
+
+    /// Checks that the winners are drawn and the prize of `tier` is still unclaimed.
+    pub fn expect_prize_claimable(&self, tier: u8) -> Result<()> {
+        self.expect_status(LotteryStatus::WinnerRevealed, ErrorCode::WinnerNotChosen)?;
+        require!(!self.is_tier_claimed(tier), ErrorCode::PrizeAlreadyClaimed);
+        Ok(())
+    }
+
+    pub fn is_tier_claimed(&self, tier: u8) -> bool {
+        self.claimed_tiers & (1 << tier) != 0
+    }
+
+    pub fn mark_tier_claimed(&mut self, tier: u8) {
+        self.claimed_tiers |= 1 << tier;
+    }
+
+    pub fn all_tiers_claimed(&self) -> bool {
+        (0..self.winners.len() as u8).all(|tier| self.is_tier_claimed(tier))
+    }
+
+    /// Amount of the prize pool owed to `tier`. Shares of tiers left empty because fewer
+    /// tickets than tiers were sold are spread proportionally over the filled ones.
+    pub fn tier_share(&self, tier: u8) -> Result<u64> {
+        let filled_basis_points: u128 = self.prize_tiers[..self.winners.len()]
+            .iter()
+            .map(|share| *share as u128)
+            .sum();
+        let tier_basis_points = *self
+            .prize_tiers
+            .get(tier as usize)
+            .ok_or(ErrorCode::InvalidPrizeTier)? as u128;
+
+        let share = (self.prize_pool as u128)
+            .checked_mul(tier_basis_points)
+            .and_then(|amount| amount.checked_div(filled_basis_points))
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        Ok(share as u64)
+    }
+}
+
+#[event]
+pub struct LotteryHadNoParticipants {
+    pub lottery_id: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct RandomnessAbandoned {
+    pub lottery_id: u64,
+    pub randomness_account: Pubkey,
+    pub slot: u64,
+}
+
+#[event]
+pub struct RandomnessRecommitted {
+    pub lottery_id: u64,
+    pub stale_randomness_account: Pubkey,
+    pub randomness_account: Pubkey,
+    pub slot: u64,
+}
+
+#[event]
+pub struct AuthorityTransferred {
+    pub lottery_id: u64,
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+/// Checks that the recorded pot is fully backed: by the lamports held by the lottery
+/// account above its rent-exempt minimum for SOL lotteries, or by the vault balance for
+/// SPL lotteries. Extra funds (e.g. direct transfers) are allowed.
+pub fn assert_pot_invariant(
+    token_lottery: &Account<TokenLottery>,
+    vault: Option<&InterfaceAccount<TokenAccount>>,
+) -> Result<()> {
+    if !token_lottery.pays_in_sol() {
+        let vault = vault.ok_or(ErrorCode::MissingPaymentAccounts)?;
+        require!(
+            vault.amount >= token_lottery.lottery_pot_amount,
+            ErrorCode::PotInvariantViolated
+        );
+        return Ok(());
+    }
+
+    let info = token_lottery.to_account_info();
+    let rent_exempt_minimum = Rent::get()?.minimum_balance(info.data_len());
+    let available = info
+        .lamports()
+        .checked_sub(rent_exempt_minimum)
+        .ok_or(ErrorCode::PotInvariantViolated)?;
+
+    require!(
+        available >= token_lottery.lottery_pot_amount,
+        ErrorCode::PotInvariantViolated
+    );
+
+    Ok(())
+}
+
+/// Charges the buyer for `quantity` tickets and credits the pot. With a transfer fee the
+/// pot only grows by what actually reached the vault.
+#[allow(clippy::too_many_arguments)]
+pub fn pay_for_tickets<'info>(
+    token_lottery: &mut Account<'info, TokenLottery>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    payment_mint: Option<&InterfaceAccount<'info, Mint>>,
+    mut vault: Option<&mut InterfaceAccount<'info, TokenAccount>>,
+    payer_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    payment_token_program: Option<&Interface<'info, TokenInterface>>,
+    hook_accounts: &[AccountInfo<'info>],
+    quantity: u64,
+) -> Result<()> {
+    let ticket_price = token_lottery
+        .ticket_price
+        .checked_mul(quantity)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    let token_payment = TokenPayment::for_lottery(
+        token_lottery,
+        payment_mint,
+        vault.as_deref(),
+        payer_token_account,
+        payment_token_program,
+        hook_accounts,
+    )?;
+    let received = collect_payment(
+        token_lottery,
+        payer,
+        system_program,
+        token_payment,
+        ticket_price,
+    )?;
+    if let Some(vault) = vault.as_mut() {
+        vault.reload()?;
+    }
+
+    token_lottery.credit_pot(received)?;
+    assert_pot_invariant(token_lottery, vault.as_deref())
+}
+
+/// Pays the prize of `tier` to `winner` from the vault (or the lottery account for SOL
+/// lotteries). The handler checks `expect_prize_claimable` and the winning ticket first.
+#[allow(clippy::too_many_arguments)]
+pub fn claim_prize<'info>(
+    token_lottery: &mut Account<'info, TokenLottery>,
+    winner: &AccountInfo<'info>,
+    payment_mint: Option<&InterfaceAccount<'info, Mint>>,
+    vault: Option<&InterfaceAccount<'info, TokenAccount>>,
+    winner_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    payment_token_program: Option<&Interface<'info, TokenInterface>>,
+    hook_accounts: &[AccountInfo<'info>],
+    tier: u8,
+) -> Result<()> {
+    let token_payment = TokenPayment::for_lottery(
+        token_lottery,
+        payment_mint,
+        vault,
+        winner_token_account,
+        payment_token_program,
+        hook_accounts,
+    )?;
+    pay_prize(token_lottery, winner, token_payment, tier)
+}
+
+/// Pays the prize of `tier` to `winner`, whose winning ticket has been verified.
+pub fn pay_prize<'info>(
+    token_lottery: &mut Account<'info, TokenLottery>,
+    winner: &AccountInfo<'info>,
+    token_payment: Option<TokenPayment<'_, 'info>>,
+    tier: u8,
+) -> Result<()> {
+    // The pot must be fully backed by the lottery account (or vault) before anything
+    // leaves it
+    assert_pot_invariant(
+        token_lottery,
+        token_payment.as_ref().map(|payment| payment.vault),
+    )?;
+
+    // The advertised prize is what the winner receives; any transfer fee on the payout
+    // comes out of the tier's share on top of it
+    let share = token_lottery.tier_share(tier)?;
+    let prize = match token_payment.as_ref() {
+        Some(payment) => amount_after_fee(payment.mint, share)?,
+        None => share,
+    };
+    msg!("Tier {} prize: {}", tier, prize);
+
+    let paid = pay_out(token_lottery, winner, token_payment, prize)?;
+    token_lottery.debit_pot(paid)?;
+    token_lottery.mark_tier_claimed(tier);
+    if token_lottery.all_tiers_claimed() {
+        token_lottery.status = LotteryStatus::Claimed;
+    }
+
+    Ok(())
+}
+
+/// Pays the crank bounty out of the pot to `cranker`. Like prizes, the bounty is what the
+/// cranker receives and any transfer fee comes out of the pot on top of it.
+pub fn pay_crank_bounty<'info>(
+    token_lottery: &mut Account<'info, TokenLottery>,
+    cranker: &AccountInfo<'info>,
+    token_payment: Option<TokenPayment<'_, 'info>>,
+) -> Result<()> {
+    assert_pot_invariant(
+        token_lottery,
+        token_payment.as_ref().map(|payment| payment.vault),
+    )?;
+
+    let reward = token_lottery.crank_reward();
+    let bounty = match token_payment.as_ref() {
+        Some(payment) => amount_after_fee(payment.mint, reward)?,
+        None => reward,
+    };
+    msg!("Crank bounty: {}", bounty);
+
+    let paid = pay_out(token_lottery, cranker, token_payment, bounty)?;
+    token_lottery.debit_pot(paid)
+}
+
+/// Refunds the pot share of `quantity` burned tickets starting at `first_ticket` to
+/// `holder`.
+pub fn pay_refund<'info>(
+    token_lottery: &mut Account<'info, TokenLottery>,
+    holder: &AccountInfo<'info>,
+    token_payment: Option<TokenPayment<'_, 'info>>,
+    first_ticket: u64,
+    quantity: u64,
+) -> Result<()> {
+    assert_pot_invariant(
+        token_lottery,
+        token_payment.as_ref().map(|payment| payment.vault),
+    )?;
+
+    let share = token_lottery.refund_share(quantity)?;
+    let refund = match token_payment.as_ref() {
+        Some(payment) => amount_after_fee(payment.mint, share)?,
+        None => share,
+    };
+    msg!(
+        "Refund for {} tickets from #{}: {}",
+        quantity,
+        first_ticket,
+        refund
+    );
+
+    let paid = pay_out(token_lottery, holder, token_payment, refund)?;
+    token_lottery.debit_pot(paid)?;
+    token_lottery.tickets_refunded = token_lottery
+        .tickets_refunded
+        .checked_add(quantity)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    Ok(())
 }
 
 #[error_code]
@@ -646,4 +3674,264 @@ pub enum ErrorCode {
     NotVerifiedTicket,
     #[msg("Incorrect Ticket")]
     IncorrectTicket,
+    #[msg("Arithmetic overflow")]
+    ArithmeticOverflow,
+    #[msg("Lottery pot does not hold enough funds")]
+    InsufficientPot,
+    #[msg("Lottery pot is not backed by the lottery account balance")]
+    PotInvariantViolated,
+    #[msg("Payment mint, vault, token account or token program missing")]
+    MissingPaymentAccounts,
+    #[msg("Payment mint has an extension the lottery does not support")]
+    UnsupportedPaymentMint,
+    #[msg("Prize tiers must be non-zero basis point shares summing to 10000")]
+    InvalidPrizeTiers,
+    #[msg("Prize tier has no winner")]
+    InvalidPrizeTier,
+    #[msg("Prize for this tier has already been claimed")]
+    PrizeAlreadyClaimed,
+    #[msg("Refunds are not available for this lottery")]
+    RefundsNotAvailable,
+    #[msg("Lottery has participants")]
+    LotteryHasParticipants,
+    #[msg("Lottery collection has already been initialized")]
+    CollectionAlreadyInitialized,
+    #[msg("Randomness has not been committed")]
+    RandomnessNotCommitted,
+    #[msg("Lottery can no longer be cancelled")]
+    LotteryNotCancellable,
+    #[msg("Ticket quantity must be at least 1")]
+    InvalidTicketQuantity,
+    #[msg("Instruction does not match the lottery's ticket mode")]
+    WrongTicketMode,
+    #[msg("Ticket tree has already been initialized")]
+    TicketTreeAlreadyInitialized,
+    #[msg("Ticket tree has not been initialized")]
+    TicketTreeNotInitialized,
+    #[msg("Collection name, symbol or URIs exceed the Metaplex limits")]
+    InvalidCollectionMetadata,
+    #[msg("A winning ticket can't be burned before its prize is claimed")]
+    PrizeNotClaimed,
+    #[msg("Lottery still has prizes to claim or tickets to refund")]
+    LotteryNotFinished,
+    #[msg("Reveal timeout must be at least one slot")]
+    InvalidRevealTimeout,
+    #[msg("Committed randomness can't be replaced before the reveal deadline")]
+    RevealDeadlineNotReached,
+    #[msg("Committed randomness must be passed unrevealed to be replaced")]
+    CannotRecommitRandomness,
+    #[msg("Randomness account is not a Switchboard randomness account")]
+    InvalidRandomnessAccount,
+    #[msg("Randomness account doesn't belong to the lottery's Switchboard queue")]
+    RandomnessQueueNotAllowed,
+    #[msg("Ticket sales are closed once randomness is committed")]
+    RandomnessAlreadyCommitted,
+    #[msg("Randomness source is not enabled in this build of the program")]
+    RandomnessSourceNotEnabled,
+    #[msg("Instruction doesn't apply to the lottery's randomness source")]
+    WrongRandomnessSource,
+    #[msg("Secret doesn't match the randomness commitment")]
+    InvalidRandomnessSecret,
+    #[msg("Crank bounty exceeds MAX_CRANK_BOUNTY_BPS")]
+    InvalidCrankBounty,
+    #[msg("Slot hash to reveal with is no longer in the SlotHashes sysvar")]
+    SlotHashExpired,
+    #[msg("Sales window must start before it ends")]
+    InvalidSalesWindow,
+    #[msg("Losing tickets are not marked: the lottery has no loser URI")]
+    LosingTicketsNotMarked,
+    #[msg("Ticket metadata account or Token Metadata program is missing")]
+    MissingMetadataAccounts,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A lottery with three tiers of 50%, 30% and 20%, paid in SOL.
+    fn lottery() -> TokenLottery {
+        TokenLottery {
+            bump: 255,
+            lottery_id: 1,
+            winners: Vec::new(),
+            status: LotteryStatus::CollectionInitialized,
+            start_time: 10,
+            end_time: 20,
+            lottery_pot_amount: 0,
+            total_tickets: 0,
+            ticket_price: 100,
+            authority: Pubkey::new_unique(),
+            randomness_account: Pubkey::default(),
+            payment_mint: Pubkey::default(),
+            prize_tiers: vec![5000, 3000, 2000],
+            prize_pool: 0,
+            claimed_tiers: 0,
+            min_tickets: 0,
+            tickets_refunded: 0,
+            ticket_mode: TicketMode::Nft,
+            merkle_tree: Pubkey::default(),
+            metadata: CollectionMetadata::default(),
+            claim_period: 50,
+            close_recipient: Pubkey::new_unique(),
+            pending_authority: Pubkey::default(),
+            crank_bounty_bps: 0,
+            reveal_timeout: 50,
+            randomness_commit_slot: 0,
+            reveal_slot: 0,
+            randomness_source: RandomnessSource::Switchboard {
+                queue: Pubkey::new_unique(),
+            },
+        }
+    }
+
+    #[test]
+    fn tiers_split_the_prize_pool_rounding_down() {
+        let mut token_lottery = lottery();
+        token_lottery.winners = vec![4, 0, 7];
+        token_lottery.prize_pool = 1001;
+
+        assert_eq!(token_lottery.tier_share(0).unwrap(), 500);
+        assert_eq!(token_lottery.tier_share(1).unwrap(), 300);
+        assert_eq!(token_lottery.tier_share(2).unwrap(), 200);
+        assert_eq!(
+            token_lottery.tier_share(3),
+            Err(ErrorCode::InvalidPrizeTier.into())
+        );
+    }
+
+    #[test]
+    fn empty_tiers_are_spread_over_the_filled_ones() {
+        let mut token_lottery = lottery();
+        token_lottery.winners = vec![1, 0];
+        token_lottery.prize_pool = 1000;
+
+        assert_eq!(token_lottery.tier_share(0).unwrap(), 625);
+        assert_eq!(token_lottery.tier_share(1).unwrap(), 375);
+    }
+
+    #[test]
+    fn crank_reward_is_a_share_of_the_pot() {
+        let mut token_lottery = lottery();
+        token_lottery.lottery_pot_amount = 10_000;
+        assert_eq!(token_lottery.crank_reward(), 0);
+
+        token_lottery.crank_bounty_bps = MAX_CRANK_BOUNTY_BPS;
+        assert_eq!(token_lottery.crank_reward(), 100);
+
+        token_lottery.lottery_pot_amount = 99;
+        assert_eq!(token_lottery.crank_reward(), 0);
+    }
+
+    #[test]
+    fn the_last_refund_takes_the_remainder() {
+        let mut token_lottery = lottery();
+        token_lottery.total_tickets = 3;
+        // A transfer fee left the pot short of three ticket prices
+        token_lottery.lottery_pot_amount = 299;
+
+        let mut refunded = Vec::new();
+        for _ in 0..3 {
+            let share = token_lottery.refund_share(1).unwrap();
+            token_lottery.debit_pot(share).unwrap();
+            token_lottery.tickets_refunded += 1;
+            refunded.push(share);
+        }
+
+        assert_eq!(refunded, vec![99, 100, 100]);
+        assert_eq!(token_lottery.lottery_pot_amount, 0);
+        assert_eq!(
+            token_lottery.refund_share(1),
+            Err(ErrorCode::RefundsNotAvailable.into())
+        );
+    }
+
+    #[test]
+    fn ranges_are_refunded_whole() {
+        let mut token_lottery = lottery();
+        token_lottery.total_tickets = 4;
+        token_lottery.lottery_pot_amount = 400;
+
+        assert_eq!(token_lottery.refund_share(3).unwrap(), 300);
+        assert_eq!(
+            token_lottery.refund_share(5),
+            Err(ErrorCode::RefundsNotAvailable.into())
+        );
+    }
+
+    #[test]
+    fn the_clock_opens_and_closes_sales() {
+        let mut token_lottery = lottery();
+
+        token_lottery.sync_status(9);
+        assert_eq!(token_lottery.status, LotteryStatus::CollectionInitialized);
+        token_lottery.sync_status(10);
+        assert_eq!(token_lottery.status, LotteryStatus::Open);
+
+        token_lottery.total_tickets = 5;
+        token_lottery.sync_status(20);
+        assert_eq!(token_lottery.status, LotteryStatus::Closed);
+    }
+
+    #[test]
+    fn undersubscribed_lotteries_move_to_refunds() {
+        let mut token_lottery = lottery();
+        token_lottery.status = LotteryStatus::Open;
+        token_lottery.min_tickets = 3;
+        token_lottery.total_tickets = 2;
+
+        token_lottery.sync_status(20);
+        assert_eq!(token_lottery.status, LotteryStatus::Refunding);
+
+        // Selling nothing is not undersubscribed: the lottery settles without participants
+        let mut token_lottery = lottery();
+        token_lottery.status = LotteryStatus::Open;
+        token_lottery.min_tickets = 3;
+
+        token_lottery.sync_status(20);
+        assert_eq!(token_lottery.status, LotteryStatus::Closed);
+        assert!(token_lottery.settle_without_participants(20));
+        assert_eq!(token_lottery.status, LotteryStatus::NoParticipants);
+    }
+
+    #[test]
+    fn unrevealed_secrets_move_to_refunds_at_the_deadline() {
+        let mut token_lottery = lottery();
+        token_lottery.status = LotteryStatus::RandomnessCommitted;
+        token_lottery.randomness_commit_slot = 100;
+
+        token_lottery.sync_status(150);
+        assert_eq!(token_lottery.status, LotteryStatus::RandomnessCommitted);
+
+        token_lottery.randomness_source = RandomnessSource::CommitReveal {
+            commitment: [0u8; 32],
+        };
+        token_lottery.sync_status(149);
+        assert_eq!(token_lottery.status, LotteryStatus::RandomnessCommitted);
+        token_lottery.sync_status(150);
+        assert_eq!(token_lottery.status, LotteryStatus::Refunding);
+    }
+
+    #[test]
+    fn closes_once_nothing_is_left_to_pay() {
+        let mut token_lottery = lottery();
+        let not_finished = Err(ErrorCode::LotteryNotFinished.into());
+
+        token_lottery.status = LotteryStatus::Open;
+        assert_eq!(token_lottery.expect_closable(30), not_finished);
+        token_lottery.status = LotteryStatus::NoParticipants;
+        assert!(token_lottery.expect_closable(30).is_ok());
+
+        token_lottery.status = LotteryStatus::Refunding;
+        token_lottery.total_tickets = 2;
+        token_lottery.tickets_refunded = 1;
+        assert_eq!(token_lottery.expect_closable(30), not_finished);
+        token_lottery.tickets_refunded = 2;
+        assert!(token_lottery.expect_closable(30).is_ok());
+
+        // The claim period runs from the draw, not from the end of sales
+        token_lottery.status = LotteryStatus::WinnerRevealed;
+        token_lottery.reveal_slot = 100;
+        assert_eq!(token_lottery.expect_closable(149), not_finished);
+        assert!(token_lottery.expect_closable(150).is_ok());
+    }
 }
@@ -0,0 +1,268 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{
+        non_transferable::NonTransferable, permanent_delegate::PermanentDelegate,
+        transfer_fee::TransferFeeConfig, transfer_hook, BaseStateWithExtensions,
+        StateWithExtensions,
+    },
+    onchain::invoke_transfer_checked,
+};
+use anchor_spl::token_2022_extensions::transfer_fee::{
+    harvest_withheld_tokens_to_mint, HarvestWithheldTokensToMint,
+};
+use anchor_spl::token_interface::{
+    close_account, CloseAccount, Mint, TokenAccount, TokenInterface,
+};
+
+use crate::{ErrorCode, TokenLottery};
+
+/// Transfer hook programs a payment mint may use. Hooks run on every ticket purchase and
+/// payout, so only programs that cannot block or redirect lottery transfers belong here.
+pub const ALLOWED_TRANSFER_HOOK_PROGRAMS: &[Pubkey] = &[];
+
+/// Token accounts used to move an SPL-denominated pot in and out of the vault.
+pub struct TokenPayment<'a, 'info> {
+    pub mint: &'a InterfaceAccount<'info, Mint>,
+    pub vault: &'a InterfaceAccount<'info, TokenAccount>,
+    /// The buyer's source account or the recipient's destination account
+    pub user_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    pub token_program: &'a Interface<'info, TokenInterface>,
+    /// Extra accounts required by an allowlisted transfer hook
+    pub hook_accounts: &'a [AccountInfo<'info>],
+}
+
+impl<'a, 'info> TokenPayment<'a, 'info> {
+    /// Returns `None` for SOL lotteries, otherwise requires every payment account.
+    pub fn for_lottery(
+        token_lottery: &TokenLottery,
+        mint: Option<&'a InterfaceAccount<'info, Mint>>,
+        vault: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+        user_token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+        token_program: Option<&'a Interface<'info, TokenInterface>>,
+        hook_accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Option<Self>> {
+        if token_lottery.pays_in_sol() {
+            return Ok(None);
+        }
+
+        match (mint, vault, user_token_account, token_program) {
+            (Some(mint), Some(vault), Some(user_token_account), Some(token_program)) => {
+                Ok(Some(Self {
+                    mint,
+                    vault,
+                    user_token_account,
+                    token_program,
+                    hook_accounts,
+                }))
+            }
+            _ => err!(ErrorCode::MissingPaymentAccounts),
+        }
+    }
+}
+
+/// Rejects payment mints whose Token-2022 extensions would break the lottery: tokens that
+/// can't be paid out (non-transferable), a delegate that could drain the vault (permanent
+/// delegate), or transfer hooks outside [`ALLOWED_TRANSFER_HOOK_PROGRAMS`].
+pub fn validate_payment_mint(mint: &InterfaceAccount<Mint>) -> Result<()> {
+    let mint_info = mint.to_account_info();
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+
+    require!(
+        mint_state.get_extension::<NonTransferable>().is_err(),
+        ErrorCode::UnsupportedPaymentMint
+    );
+
+    if let Ok(permanent_delegate) = mint_state.get_extension::<PermanentDelegate>() {
+        require!(
+            Option::<Pubkey>::from(permanent_delegate.delegate).is_none(),
+            ErrorCode::UnsupportedPaymentMint
+        );
+    }
+
+    if let Some(hook_program) = transfer_hook::get_program_id(&mint_state) {
+        require!(
+            ALLOWED_TRANSFER_HOOK_PROGRAMS.contains(&hook_program),
+            ErrorCode::UnsupportedPaymentMint
+        );
+    }
+
+    Ok(())
+}
+
+fn transfer_fee_config(mint: &InterfaceAccount<Mint>) -> Result<Option<TransferFeeConfig>> {
+    let mint_info = mint.to_account_info();
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+
+    Ok(mint_state
+        .get_extension::<TransferFeeConfig>()
+        .ok()
+        .copied())
+}
+
+/// Amount that arrives at the destination when `amount` is sent, after any transfer fee.
+pub fn amount_after_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let Some(fee_config) = transfer_fee_config(mint)? else {
+        return Ok(amount);
+    };
+
+    let fee = fee_config
+        .calculate_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    amount
+        .checked_sub(fee)
+        .ok_or(ErrorCode::ArithmeticOverflow.into())
+}
+
+/// Amount that has to be sent for `net_amount` to arrive, including any transfer fee.
+pub fn amount_before_fee(mint: &InterfaceAccount<Mint>, net_amount: u64) -> Result<u64> {
+    let Some(fee_config) = transfer_fee_config(mint)? else {
+        return Ok(net_amount);
+    };
+
+    let fee = fee_config
+        .calculate_inverse_epoch_fee(Clock::get()?.epoch, net_amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    net_amount
+        .checked_add(fee)
+        .ok_or(ErrorCode::ArithmeticOverflow.into())
+}
+
+/// Moves `amount` from the buyer into the pot: lamports into the lottery account for SOL
+/// lotteries, tokens into the vault otherwise. Returns the amount actually received, which
+/// is less than `amount` for mints that charge a transfer fee.
+pub fn collect_payment<'info>(
+    token_lottery: &Account<'info, TokenLottery>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    token_payment: Option<TokenPayment<'_, 'info>>,
+    amount: u64,
+) -> Result<u64> {
+    let Some(payment) = token_payment else {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: token_lottery.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        return Ok(amount);
+    };
+
+    validate_payment_mint(payment.mint)?;
+    let received = amount_after_fee(payment.mint, amount)?;
+
+    invoke_transfer_checked(
+        payment.token_program.key,
+        payment.user_token_account.to_account_info(),
+        payment.mint.to_account_info(),
+        payment.vault.to_account_info(),
+        payer.to_account_info(),
+        payment.hook_accounts,
+        amount,
+        payment.mint.decimals,
+        &[],
+    )?;
+
+    Ok(received)
+}
+
+/// Pays `net_amount` out of the pot to `recipient` (SOL) or to the payment's user token
+/// account (SPL), grossing the transfer up so the recipient receives `net_amount` after
+/// any transfer fee. Returns the amount that left the pot; the caller is responsible for
+/// debiting it from `lottery_pot_amount`.
+pub fn pay_out<'info>(
+    token_lottery: &Account<'info, TokenLottery>,
+    recipient: &AccountInfo<'info>,
+    token_payment: Option<TokenPayment<'_, 'info>>,
+    net_amount: u64,
+) -> Result<u64> {
+    let Some(payment) = token_payment else {
+        **token_lottery.to_account_info().try_borrow_mut_lamports()? -= net_amount;
+        **recipient.try_borrow_mut_lamports()? += net_amount;
+        return Ok(net_amount);
+    };
+
+    let gross_amount = amount_before_fee(payment.mint, net_amount)?;
+    let lottery_id_bytes = token_lottery.lottery_id.to_le_bytes();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"token_lottery".as_ref(),
+        lottery_id_bytes.as_ref(),
+        &[token_lottery.bump],
+    ]];
+
+    invoke_transfer_checked(
+        payment.token_program.key,
+        payment.vault.to_account_info(),
+        payment.mint.to_account_info(),
+        payment.user_token_account.to_account_info(),
+        token_lottery.to_account_info(),
+        payment.hook_accounts,
+        gross_amount,
+        payment.mint.decimals,
+        signer_seeds,
+    )?;
+
+    Ok(gross_amount)
+}
+
+/// Sends everything left in the vault to the user token account of `payment`, then closes
+/// the vault with its rent going to `rent_recipient`. Transfer fees withheld in the vault
+/// are harvested to the mint first, since Token-2022 won't close an account holding them.
+pub fn drain_vault<'info>(
+    token_lottery: &Account<'info, TokenLottery>,
+    payment: TokenPayment<'_, 'info>,
+    rent_recipient: &AccountInfo<'info>,
+) -> Result<()> {
+    let lottery_id_bytes = token_lottery.lottery_id.to_le_bytes();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"token_lottery".as_ref(),
+        lottery_id_bytes.as_ref(),
+        &[token_lottery.bump],
+    ]];
+
+    if payment.vault.amount > 0 {
+        invoke_transfer_checked(
+            payment.token_program.key,
+            payment.vault.to_account_info(),
+            payment.mint.to_account_info(),
+            payment.user_token_account.to_account_info(),
+            token_lottery.to_account_info(),
+            payment.hook_accounts,
+            payment.vault.amount,
+            payment.mint.decimals,
+            signer_seeds,
+        )?;
+    }
+
+    if transfer_fee_config(payment.mint)?.is_some() {
+        harvest_withheld_tokens_to_mint(
+            CpiContext::new(
+                payment.token_program.to_account_info(),
+                HarvestWithheldTokensToMint {
+                    token_program_id: payment.token_program.to_account_info(),
+                    mint: payment.mint.to_account_info(),
+                },
+            ),
+            vec![payment.vault.to_account_info()],
+        )?;
+    }
+
+    close_account(CpiContext::new_with_signer(
+        payment.token_program.to_account_info(),
+        CloseAccount {
+            account: payment.vault.to_account_info(),
+            destination: rent_recipient.clone(),
+            authority: token_lottery.to_account_info(),
+        },
+        signer_seeds,
+    ))
+}
@@ -0,0 +1,564 @@
+#[cfg(feature = "switchboard")]
+use std::cell::Ref;
+
+use anchor_lang::prelude::*;
+#[cfg(feature = "commit-reveal")]
+use anchor_lang::solana_program::sysvar::slot_hashes;
+#[cfg(feature = "commit-reveal")]
+use solana_sha256_hasher::hashv;
+#[cfg(feature = "switchboard")]
+use switchboard_on_demand::{RandomnessAccountData, ON_DEMAND_DEVNET_PID, ON_DEMAND_MAINNET_PID};
+
+use crate::ErrorCode;
+
+/// Where a lottery's winning randomness comes from. Every variant is always part of the
+/// account layout; a program built without the matching cargo feature rejects it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum RandomnessSource {
+    /// Switchboard on-demand randomness from `queue` (feature `switchboard`)
+    Switchboard { queue: Pubkey },
+    /// An ORAO VRF `Randomness` request account (feature `orao`)
+    Orao,
+    /// A secret whose SHA-256 is `commitment`, mixed at reveal with the hash of a slot
+    /// produced after `commit_randomness` (feature `commit-reveal`). Trusts the operator:
+    /// the secret holder sees the outcome first and can veto it by not revealing.
+    CommitReveal { commitment: [u8; 32] },
+}
+
+/// Slots between a commit-reveal commit and the slot whose hash is mixed into the draw.
+/// Leaders produce 4 consecutive slots, so the slot always falls in a later leader window
+/// than the commit and its hash is unknown to whoever committed.
+pub const SLOT_HASH_DELAY: u64 = 4;
+
+impl RandomnessSource {
+    /// Fails unless the program was built with the feature of this source.
+    pub fn ensure_enabled(&self) -> Result<()> {
+        match self {
+            #[cfg(feature = "switchboard")]
+            Self::Switchboard { .. } => Ok(()),
+            #[cfg(feature = "orao")]
+            Self::Orao => Ok(()),
+            #[cfg(feature = "commit-reveal")]
+            Self::CommitReveal { .. } => Ok(()),
+            #[allow(unreachable_patterns)]
+            _ => err!(ErrorCode::RandomnessSourceNotEnabled),
+        }
+    }
+
+    /// Slots after a commit before the randomness can be revealed at the earliest.
+    pub fn reveal_delay(&self) -> u64 {
+        match self {
+            Self::CommitReveal { .. } => SLOT_HASH_DELAY,
+            _ => 0,
+        }
+    }
+
+    /// Whether randomness left unrevealed past the reveal deadline sends the lottery into
+    /// refunds rather than being replaced. Only the secret holder can reveal a
+    /// commit-reveal draw, so it can't be recommitted without them; this keeps the pot
+    /// from being locked but leaves them a veto over the outcome.
+    pub fn refunds_on_timeout(&self) -> bool {
+        matches!(self, Self::CommitReveal { .. })
+    }
+
+    /// Checks `account` holds randomness nobody can know yet at `slot`. Commit-reveal
+    /// lotteries pass the `SlotHashes` sysvar instead, read again at reveal.
+    pub fn commit(&mut self, account: &AccountInfo, slot: u64) -> Result<()> {
+        match self {
+            #[cfg(feature = "switchboard")]
+            Self::Switchboard { queue } => {
+                let randomness = load_randomness(account, queue)?;
+
+                // if we already passed the slot it means the data was already revealed and
+                // someone could know who the winner would be
+                require!(
+                    randomness.seed_slot == slot - 1,
+                    ErrorCode::RandomnessAlreadyRevealed
+                );
+                Ok(())
+            }
+            #[cfg(feature = "orao")]
+            Self::Orao => {
+                require!(
+                    !load_orao_randomness(account)?.is_fulfilled(),
+                    ErrorCode::RandomnessAlreadyRevealed
+                );
+                Ok(())
+            }
+            #[cfg(feature = "commit-reveal")]
+            Self::CommitReveal { .. } => {
+                require_keys_eq!(
+                    *account.key,
+                    slot_hashes::ID,
+                    ErrorCode::InvalidRandomnessAccount
+                );
+                Ok(())
+            }
+            #[allow(unreachable_patterns)]
+            _ => {
+                let _ = (account, slot);
+                err!(ErrorCode::RandomnessSourceNotEnabled)
+            }
+        }
+    }
+
+    /// Whether the randomness committed at `commit_slot` can never be revealed: the
+    /// account was closed or no longer carries the committed seed.
+    pub fn is_abandoned(&self, account: &AccountInfo, commit_slot: u64) -> bool {
+        match self {
+            #[cfg(feature = "switchboard")]
+            Self::Switchboard { queue } => match load_randomness(account, queue) {
+                Ok(randomness) => randomness.seed_slot != commit_slot - 1,
+                Err(_) => true,
+            },
+            #[cfg(feature = "orao")]
+            Self::Orao => load_orao_randomness(account).is_err(),
+            #[allow(unreachable_patterns)]
+            _ => {
+                let _ = (account, commit_slot);
+                false
+            }
+        }
+    }
+
+    /// Checks the randomness committed at `commit_slot` is still unrevealed, so replacing
+    /// it can't let the committer choose between known outcomes.
+    pub fn ensure_unrevealed(&self, account: &AccountInfo, commit_slot: u64) -> Result<()> {
+        match self {
+            #[cfg(feature = "switchboard")]
+            Self::Switchboard { queue } => {
+                let randomness = load_randomness(account, queue)?;
+
+                // Committed again on Switchboard, it may hide a revealed value
+                require!(
+                    randomness.seed_slot == commit_slot - 1
+                        && randomness.reveal_slot <= randomness.seed_slot,
+                    ErrorCode::CannotRecommitRandomness
+                );
+                Ok(())
+            }
+            #[cfg(feature = "orao")]
+            Self::Orao => {
+                require!(
+                    !load_orao_randomness(account)?.is_fulfilled(),
+                    ErrorCode::CannotRecommitRandomness
+                );
+                Ok(())
+            }
+            // Commit-reveal lotteries move to refunds at the deadline instead
+            #[cfg(feature = "commit-reveal")]
+            Self::CommitReveal { .. } => err!(ErrorCode::CannotRecommitRandomness),
+            #[allow(unreachable_patterns)]
+            _ => {
+                let _ = (account, commit_slot);
+                err!(ErrorCode::RandomnessSourceNotEnabled)
+            }
+        }
+    }
+
+    /// Randomness the winners are drawn from, read from the account committed at
+    /// `commit_slot`. Commit-reveal lotteries are revealed with `reveal_secret` instead.
+    pub fn revealed_value(
+        &self,
+        account: &AccountInfo,
+        commit_slot: u64,
+        slot: u64,
+    ) -> Result<[u8; 32]> {
+        match self {
+            #[cfg(feature = "switchboard")]
+            Self::Switchboard { queue } => {
+                let randomness = load_randomness(account, queue)?;
+
+                // The randomness account's authority can commit it again on Switchboard;
+                // only the seed committed to the lottery may be drawn from
+                require!(
+                    randomness.seed_slot == commit_slot - 1,
+                    ErrorCode::RandomnessAlreadyRevealed
+                );
+
+                Ok(randomness
+                    .get_value(slot)
+                    .map_err(|_| ErrorCode::RandomnessNotResolved)?)
+            }
+            #[cfg(feature = "orao")]
+            Self::Orao => {
+                let randomness = load_orao_randomness(account)?;
+                require!(randomness.is_fulfilled(), ErrorCode::RandomnessNotResolved);
+
+                let mut value = [0u8; 32];
+                value.copy_from_slice(&randomness.randomness[..32]);
+                Ok(value)
+            }
+            #[cfg(feature = "commit-reveal")]
+            Self::CommitReveal { .. } => err!(ErrorCode::WrongRandomnessSource),
+            #[allow(unreachable_patterns)]
+            _ => {
+                let _ = (account, commit_slot, slot);
+                err!(ErrorCode::RandomnessSourceNotEnabled)
+            }
+        }
+    }
+
+    /// Randomness of a commit-reveal lottery: the secret matching the commitment, mixed
+    /// with the hash of the first slot produced `SLOT_HASH_DELAY` slots after
+    /// `commit_slot`, read from the `SlotHashes` sysvar.
+    pub fn value_from_secret(
+        &self,
+        secret: &[u8; 32],
+        slot_hashes: &AccountInfo,
+        commit_slot: u64,
+    ) -> Result<[u8; 32]> {
+        match self {
+            #[cfg(feature = "commit-reveal")]
+            Self::CommitReveal { commitment } => {
+                require!(
+                    hashv(&[secret]).to_bytes() == *commitment,
+                    ErrorCode::InvalidRandomnessSecret
+                );
+                let slot_hash =
+                    slot_hash_from(slot_hashes, commit_slot.saturating_add(SLOT_HASH_DELAY))?;
+                Ok(hashv(&[secret, &slot_hash]).to_bytes())
+            }
+            #[allow(unreachable_patterns)]
+            Self::CommitReveal { .. } => {
+                let _ = (secret, slot_hashes, commit_slot);
+                err!(ErrorCode::RandomnessSourceNotEnabled)
+            }
+            _ => err!(ErrorCode::WrongRandomnessSource),
+        }
+    }
+}
+
+/// Switchboard on-demand programs a randomness account may be owned by, mainnet then
+/// devnet. Each is deployed on a single cluster, so accepting both is safe.
+#[cfg(feature = "switchboard")]
+pub const SWITCHBOARD_PROGRAM_IDS: [Pubkey; 2] = [
+    Pubkey::new_from_array(ON_DEMAND_MAINNET_PID.to_bytes()),
+    Pubkey::new_from_array(ON_DEMAND_DEVNET_PID.to_bytes()),
+];
+
+/// Parses `account` as Switchboard randomness, checking it is owned by the Switchboard
+/// on-demand program and belongs to the lottery's `queue`.
+#[cfg(feature = "switchboard")]
+pub fn load_randomness<'a>(
+    account: &'a AccountInfo,
+    queue: &Pubkey,
+) -> Result<Ref<'a, RandomnessAccountData>> {
+    require!(
+        SWITCHBOARD_PROGRAM_IDS.contains(account.owner),
+        ErrorCode::InvalidRandomnessAccount
+    );
+
+    let randomness = RandomnessAccountData::parse(account.data.borrow())
+        .map_err(|_| ErrorCode::InvalidRandomnessAccount)?;
+    require!(
+        randomness.queue.to_bytes() == queue.to_bytes(),
+        ErrorCode::RandomnessQueueNotAllowed
+    );
+
+    Ok(randomness)
+}
+
+/// ORAO VRF program.
+#[cfg(feature = "orao")]
+pub const ORAO_VRF_PROGRAM_ID: Pubkey = pubkey!("VRFzZoJdhFWL8rkvu87LpKM3RbcVezpMEc6X5GVDr7y");
+
+/// Anchor discriminator of ORAO's `Randomness` account, `sha256("account:Randomness")`.
+#[cfg(feature = "orao")]
+const ORAO_RANDOMNESS_DISCRIMINATOR: [u8; 8] = [188, 96, 216, 248, 93, 94, 49, 112];
+
+/// Fixed head of an ORAO `Randomness` request account: the request seed, then the
+/// randomness, zeroed until the request is fulfilled. Read by hand so the program doesn't
+/// depend on the ORAO crate.
+#[cfg(feature = "orao")]
+pub struct OraoRandomness {
+    pub seed: [u8; 32],
+    pub randomness: [u8; 64],
+}
+
+#[cfg(feature = "orao")]
+impl OraoRandomness {
+    pub fn is_fulfilled(&self) -> bool {
+        self.randomness != [0u8; 64]
+    }
+}
+
+/// Parses `account` as an ORAO `Randomness` request owned by the ORAO VRF program.
+#[cfg(feature = "orao")]
+pub fn load_orao_randomness(account: &AccountInfo) -> Result<OraoRandomness> {
+    require_keys_eq!(
+        *account.owner,
+        ORAO_VRF_PROGRAM_ID,
+        ErrorCode::InvalidRandomnessAccount
+    );
+
+    let data = account.try_borrow_data()?;
+    require!(
+        data.len() >= 8 + 32 + 64 && data[..8] == ORAO_RANDOMNESS_DISCRIMINATOR,
+        ErrorCode::InvalidRandomnessAccount
+    );
+
+    let mut randomness = OraoRandomness {
+        seed: [0u8; 32],
+        randomness: [0u8; 64],
+    };
+    randomness.seed.copy_from_slice(&data[8..40]);
+    randomness.randomness.copy_from_slice(&data[40..104]);
+    Ok(randomness)
+}
+
+/// Hash of the first slot produced at or after `slot`, read from the `SlotHashes`
+/// sysvar: a `u64` entry count, then `(slot, hash)` entries newest first. Skipped slots
+/// have no entry. Fails until that slot is produced, and once it has left the sysvar, so
+/// waiting can never select a later hash.
+#[cfg(feature = "commit-reveal")]
+fn slot_hash_from(slot_hashes: &AccountInfo, slot: u64) -> Result<[u8; 32]> {
+    require_keys_eq!(
+        *slot_hashes.key,
+        slot_hashes::ID,
+        ErrorCode::InvalidRandomnessAccount
+    );
+
+    let data = slot_hashes.try_borrow_data()?;
+    let count = data
+        .get(..8)
+        .map(|count| u64::from_le_bytes(count.try_into().unwrap()) as usize)
+        .ok_or(ErrorCode::InvalidRandomnessAccount)?;
+    let entries = data
+        .get(8..)
+        .and_then(|entries| entries.get(..count.checked_mul(40)?))
+        .ok_or(ErrorCode::InvalidRandomnessAccount)?;
+
+    let mut found = None;
+    for entry in entries.chunks_exact(40) {
+        if u64::from_le_bytes(entry[..8].try_into().unwrap()) < slot {
+            // The entry before this one is the first slot produced at or after `slot`
+            return found.ok_or(ErrorCode::RandomnessNotResolved.into());
+        }
+        found = Some(entry[8..].try_into().unwrap());
+    }
+
+    // Every entry is at or after `slot`: the first one produced may have aged out
+    err!(ErrorCode::SlotHashExpired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(feature = "switchboard", feature = "orao", feature = "commit-reveal"))]
+    fn account<'a>(
+        key: &'a Pubkey,
+        owner: &'a Pubkey,
+        lamports: &'a mut u64,
+        data: &'a mut [u8],
+    ) -> AccountInfo<'a> {
+        AccountInfo::new(key, false, false, lamports, data, owner, false, 0)
+    }
+
+    #[test]
+    fn sources_need_their_feature() {
+        let commit_reveal = RandomnessSource::CommitReveal {
+            commitment: [0u8; 32],
+        };
+        let switchboard = RandomnessSource::Switchboard {
+            queue: Pubkey::new_unique(),
+        };
+
+        assert_eq!(
+            switchboard.ensure_enabled().is_ok(),
+            cfg!(feature = "switchboard")
+        );
+        assert_eq!(
+            RandomnessSource::Orao.ensure_enabled().is_ok(),
+            cfg!(feature = "orao")
+        );
+        assert_eq!(
+            commit_reveal.ensure_enabled().is_ok(),
+            cfg!(feature = "commit-reveal")
+        );
+    }
+
+    #[cfg(feature = "switchboard")]
+    mod switchboard {
+        use switchboard_on_demand::Discriminator;
+
+        use super::*;
+
+        /// Account data of a randomness account on `queue` seeded at `seed_slot`, as 8-byte
+        /// words so the struct after the discriminator is aligned the way the runtime aligns
+        /// account data.
+        fn randomness_data(queue: &Pubkey, seed_slot: u64) -> Vec<u64> {
+            let mut randomness: RandomnessAccountData = bytemuck::Zeroable::zeroed();
+            randomness.queue = queue.to_bytes().into();
+            randomness.seed_slot = seed_slot;
+
+            let mut data = vec![0u64; 1 + std::mem::size_of::<RandomnessAccountData>() / 8];
+            let bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut data);
+            bytes[..8].copy_from_slice(RandomnessAccountData::DISCRIMINATOR);
+            bytes[8..].copy_from_slice(bytemuck::bytes_of(&randomness));
+            data
+        }
+
+        fn load(owner: &Pubkey, data: &mut [u64], queue: &Pubkey) -> Result<()> {
+            let key = Pubkey::new_unique();
+            let mut lamports = 0;
+            let account = account(&key, owner, &mut lamports, bytemuck::cast_slice_mut(data));
+            load_randomness(&account, queue).map(|_| ())
+        }
+
+        #[test]
+        fn accepts_switchboard_randomness_on_the_queue() {
+            let queue = Pubkey::new_unique();
+            let mut data = randomness_data(&queue, 0);
+
+            assert!(load(&SWITCHBOARD_PROGRAM_IDS[0], &mut data, &queue).is_ok());
+            assert!(load(&SWITCHBOARD_PROGRAM_IDS[1], &mut data, &queue).is_ok());
+        }
+
+        #[test]
+        fn rejects_foreign_owners_queues_and_data() {
+            let queue = Pubkey::new_unique();
+            let mut data = randomness_data(&queue, 0);
+
+            assert_eq!(
+                load(&Pubkey::new_unique(), &mut data, &queue),
+                Err(ErrorCode::InvalidRandomnessAccount.into())
+            );
+            assert_eq!(
+                load(
+                    &SWITCHBOARD_PROGRAM_IDS[0],
+                    &mut data,
+                    &Pubkey::new_unique()
+                ),
+                Err(ErrorCode::RandomnessQueueNotAllowed.into())
+            );
+
+            data[0] = 0;
+            assert_eq!(
+                load(&SWITCHBOARD_PROGRAM_IDS[0], &mut data, &queue),
+                Err(ErrorCode::InvalidRandomnessAccount.into())
+            );
+        }
+
+        #[test]
+        fn randomness_committed_again_or_closed_is_abandoned() {
+            let queue = Pubkey::new_unique();
+            let source = RandomnessSource::Switchboard { queue };
+            let key = Pubkey::new_unique();
+
+            let mut lamports = 0;
+            let mut data = randomness_data(&queue, 9);
+            let committed = account(
+                &key,
+                &SWITCHBOARD_PROGRAM_IDS[0],
+                &mut lamports,
+                bytemuck::cast_slice_mut(&mut data),
+            );
+            assert!(!source.is_abandoned(&committed, 10));
+            assert!(source.ensure_unrevealed(&committed, 10).is_ok());
+
+            // Seeded again on Switchboard after the lottery committed it at slot 10
+            let mut lamports = 0;
+            let mut data = randomness_data(&queue, 11);
+            let reseeded = account(
+                &key,
+                &SWITCHBOARD_PROGRAM_IDS[0],
+                &mut lamports,
+                bytemuck::cast_slice_mut(&mut data),
+            );
+            assert!(source.is_abandoned(&reseeded, 10));
+
+            let system_program = Pubkey::default();
+            let mut lamports = 0;
+            let closed = account(&key, &system_program, &mut lamports, &mut []);
+            assert!(source.is_abandoned(&closed, 10));
+        }
+    }
+
+    #[cfg(feature = "orao")]
+    #[test]
+    fn orao_requests_reveal_once_fulfilled() {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = vec![0u8; 8 + 32 + 64 + 4];
+        data[..8].copy_from_slice(&ORAO_RANDOMNESS_DISCRIMINATOR);
+        let request = account(&key, &ORAO_VRF_PROGRAM_ID, &mut lamports, &mut data);
+
+        let mut source = RandomnessSource::Orao;
+        assert!(source.commit(&request, 10).is_ok());
+        assert_eq!(
+            source.revealed_value(&request, 10, 11),
+            Err(ErrorCode::RandomnessNotResolved.into())
+        );
+
+        request.try_borrow_mut_data().unwrap()[40..104].fill(9);
+        assert_eq!(source.revealed_value(&request, 10, 11).unwrap(), [9u8; 32]);
+        assert!(source.ensure_unrevealed(&request, 10).is_err());
+    }
+
+    /// `SlotHashes` data holding `slots`, newest first, each hashing to its own number.
+    #[cfg(feature = "commit-reveal")]
+    fn slot_hashes_data(slots: &[u64]) -> Vec<u8> {
+        let mut data = (slots.len() as u64).to_le_bytes().to_vec();
+        for slot in slots {
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(&[*slot as u8; 32]);
+        }
+        data
+    }
+
+    #[cfg(feature = "commit-reveal")]
+    #[test]
+    fn only_the_committed_secret_reveals() {
+        let secret = [7u8; 32];
+        let source = RandomnessSource::CommitReveal {
+            commitment: hashv(&[&secret]).to_bytes(),
+        };
+        let owner = Pubkey::default();
+        let mut lamports = 0;
+        let mut data = slot_hashes_data(&[16, 15, 13, 12]);
+        let slot_hashes = account(&slot_hashes::ID, &owner, &mut lamports, &mut data);
+
+        // Slot 14 was skipped, so a commit at slot 10 draws from the hash of slot 15
+        assert_eq!(
+            source.value_from_secret(&secret, &slot_hashes, 10).unwrap(),
+            hashv(&[&secret, &[15u8; 32]]).to_bytes()
+        );
+        assert_eq!(
+            source.value_from_secret(&[8u8; 32], &slot_hashes, 10),
+            Err(ErrorCode::InvalidRandomnessSecret.into())
+        );
+    }
+
+    #[cfg(feature = "commit-reveal")]
+    #[test]
+    fn reveals_only_while_the_slot_hash_is_held() {
+        let secret = [7u8; 32];
+        let source = RandomnessSource::CommitReveal {
+            commitment: hashv(&[&secret]).to_bytes(),
+        };
+        let owner = Pubkey::default();
+        let mut lamports = 0;
+        let mut data = slot_hashes_data(&[16, 15, 13, 12]);
+        let slot_hashes = account(&slot_hashes::ID, &owner, &mut lamports, &mut data);
+
+        assert_eq!(
+            source.value_from_secret(&secret, &slot_hashes, 13),
+            Err(ErrorCode::RandomnessNotResolved.into())
+        );
+        assert_eq!(
+            source.value_from_secret(&secret, &slot_hashes, 7),
+            Err(ErrorCode::SlotHashExpired.into())
+        );
+
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = slot_hashes_data(&[16, 15, 13, 12]);
+        let other = account(&key, &owner, &mut lamports, &mut data);
+        assert_eq!(
+            source.value_from_secret(&secret, &other, 10),
+            Err(ErrorCode::InvalidRandomnessAccount.into())
+        );
+    }
+}
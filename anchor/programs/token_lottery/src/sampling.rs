@@ -0,0 +1,127 @@
+//! Uniform sampling of ticket indices from the revealed randomness.
+
+use solana_sha256_hasher::hashv;
+
+/// Returns an index in `0..upper` (`upper` must be non-zero) drawn uniformly from the full
+/// 32-byte `randomness` value.
+///
+/// Each 8-byte word is mapped into range with a 128-bit wide multiply, and words that fall
+/// into the biased low region are rejected (Lemire's method). If all four words of a block
+/// are rejected, the block is rehashed and sampling continues on the new bytes.
+pub fn uniform_index(randomness: &[u8; 32], upper: u64) -> u64 {
+    // 2^64 mod upper: low products below this would make some indices more likely
+    let threshold = upper.wrapping_neg() % upper;
+    let mut block = *randomness;
+
+    loop {
+        for word in block.chunks_exact(8) {
+            let value = u64::from_le_bytes(word.try_into().unwrap());
+            let product = value as u128 * upper as u128;
+            if product as u64 >= threshold {
+                return (product >> 64) as u64;
+            }
+        }
+        block = hashv(&[block.as_ref()]).to_bytes();
+    }
+}
+
+/// Derives `count` distinct winning ticket indices out of `total_tickets` from the
+/// revealed randomness. Each tier hashes the randomness with its own tier index, and
+/// rehashes with an attempt counter when it lands on an index already drawn.
+pub fn draw_winners(randomness: &[u8; 32], total_tickets: u64, count: usize) -> Vec<u64> {
+    let mut winners: Vec<u64> = Vec::with_capacity(count);
+
+    for tier in 0..count as u8 {
+        let mut attempt: u32 = 0;
+        loop {
+            let seed = hashv(&[randomness.as_ref(), &[tier], &attempt.to_le_bytes()]).to_bytes();
+            let index = uniform_index(&seed, total_tickets);
+            if !winners.contains(&index) {
+                winners.push(index);
+                break;
+            }
+            attempt += 1;
+        }
+    }
+
+    winners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed(counter: u64) -> [u8; 32] {
+        hashv(&[b"sampling-test".as_ref(), &counter.to_le_bytes()]).to_bytes()
+    }
+
+    #[test]
+    fn single_ticket_always_wins() {
+        for counter in 0..100 {
+            assert_eq!(uniform_index(&seed(counter), 1), 0);
+        }
+    }
+
+    #[test]
+    fn indices_are_uniform_within_bounds() {
+        const UPPER: u64 = 7;
+        const SAMPLES: u64 = 70_000;
+        let mut counts = [0u64; UPPER as usize];
+
+        for counter in 0..SAMPLES {
+            counts[uniform_index(&seed(counter), UPPER) as usize] += 1;
+        }
+
+        // Expected 10_000 per bucket with a standard deviation of ~93, so 5% is > 5 sigma
+        let expected = SAMPLES / UPPER;
+        for count in counts {
+            assert!(count.abs_diff(expected) < expected / 20, "{counts:?}");
+        }
+    }
+
+    #[test]
+    fn covers_indices_beyond_the_first_byte() {
+        const UPPER: u64 = 1_000;
+        let mut seen = vec![false; UPPER as usize];
+
+        for counter in 0..20_000 {
+            seen[uniform_index(&seed(counter), UPPER) as usize] = true;
+        }
+
+        assert!(seen.iter().all(|hit| *hit));
+    }
+
+    #[test]
+    fn rejects_words_in_the_biased_region() {
+        // With upper = 2^63 + 1 the biased region is every word below 2^63 - 1, so an
+        // all-zero first word must be skipped in favour of the second one
+        let upper = (1u64 << 63) + 1;
+        let mut randomness = [0u8; 32];
+        randomness[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        assert_eq!(uniform_index(&randomness, upper), upper - 1);
+    }
+
+    #[test]
+    fn rehashes_when_every_word_is_rejected() {
+        let upper = (1u64 << 63) + 1;
+        let index = uniform_index(&[0u8; 32], upper);
+
+        assert!(index < upper);
+    }
+
+    #[test]
+    fn draws_distinct_winners_in_range() {
+        for counter in 0..200 {
+            let winners = draw_winners(&seed(counter), 5, 5);
+
+            let mut sorted = winners.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, vec![0, 1, 2, 3, 4]);
+        }
+
+        let winners = draw_winners(&seed(0), 1_000_000, 8);
+        assert_eq!(winners.len(), 8);
+        assert!(winners.iter().all(|winner| *winner < 1_000_000));
+    }
+}
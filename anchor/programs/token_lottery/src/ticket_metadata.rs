@@ -0,0 +1,200 @@
+use anchor_lang::prelude::*;
+use anchor_spl::metadata::mpl_token_metadata::{
+    MAX_NAME_LENGTH, MAX_SYMBOL_LENGTH, MAX_URI_LENGTH,
+};
+
+use crate::ErrorCode;
+
+/// Placeholder of the ticket URI templates replaced by the first ticket number.
+pub const TICKET_INDEX_PLACEHOLDER: &str = "{index}";
+
+/// Name, symbol and URIs of a lottery's collection and tickets, set when the collection is
+/// created. Lengths are bounded by the Metaplex limits so every ticket backend accepts them.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, InitSpace, Debug)]
+pub struct CollectionMetadata {
+    #[max_len(MAX_NAME_LENGTH)]
+    pub name: String,
+    #[max_len(MAX_SYMBOL_LENGTH)]
+    pub symbol: String,
+    #[max_len(MAX_URI_LENGTH)]
+    pub uri: String,
+    /// URI of the ticket NFTs, `{index}` is replaced by the first ticket number
+    #[max_len(MAX_URI_LENGTH)]
+    pub ticket_uri_template: String,
+    /// URI winning tickets are switched to by `mark_winner_metadata`, empty to keep the
+    /// ticket URI
+    #[max_len(MAX_URI_LENGTH)]
+    pub winner_uri_template: String,
+    /// URI losing tickets are switched to by `mark_winner_metadata`, empty to leave losing
+    /// tickets unmarked
+    #[max_len(MAX_URI_LENGTH)]
+    pub loser_uri_template: String,
+}
+
+impl CollectionMetadata {
+    /// Checks the lengths against the Metaplex limits. The ticket URI is checked with the
+    /// longest ticket number it can be filled in with.
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            !self.name.is_empty() && self.name.len() <= MAX_NAME_LENGTH,
+            ErrorCode::InvalidCollectionMetadata
+        );
+        require!(
+            self.symbol.len() <= MAX_SYMBOL_LENGTH,
+            ErrorCode::InvalidCollectionMetadata
+        );
+        require!(
+            self.uri.len() <= MAX_URI_LENGTH,
+            ErrorCode::InvalidCollectionMetadata
+        );
+        for template in [
+            &self.ticket_uri_template,
+            &self.winner_uri_template,
+            &self.loser_uri_template,
+        ] {
+            require!(
+                template.len() <= MAX_URI_LENGTH
+                    && fill_index(template, u64::MAX).len() <= MAX_URI_LENGTH,
+                ErrorCode::InvalidCollectionMetadata
+            );
+        }
+        Ok(())
+    }
+
+    /// Name of the NFT issued for a ticket range: `<name> #5` for a single ticket and
+    /// `<name> #5-54` for a range. The collection name is cut short when needed so the
+    /// ticket numbers always fit in the Metaplex name limit.
+    pub fn ticket_name(&self, first_ticket: u64, quantity: u64) -> Result<String> {
+        Ok(self.name_with_suffix(format!(" #{}", ticket_label(first_ticket, quantity)?)))
+    }
+
+    /// Name a winning ticket NFT is renamed to: `<name> #5-54 Winner`.
+    pub fn winning_ticket_name(&self, first_ticket: u64, quantity: u64) -> Result<String> {
+        Ok(self.name_with_suffix(format!(
+            " #{} Winner",
+            ticket_label(first_ticket, quantity)?
+        )))
+    }
+
+    /// URI of the ticket NFT whose first ticket is `first_ticket`.
+    pub fn ticket_uri(&self, first_ticket: u64) -> String {
+        fill_index(&self.ticket_uri_template, first_ticket)
+    }
+
+    /// URI of a winning ticket NFT, `None` when winners keep their ticket URI.
+    pub fn winner_uri(&self, first_ticket: u64) -> Option<String> {
+        (!self.winner_uri_template.is_empty())
+            .then(|| fill_index(&self.winner_uri_template, first_ticket))
+    }
+
+    /// URI of a losing ticket NFT, `None` when losing tickets are not marked.
+    pub fn loser_uri(&self, first_ticket: u64) -> Option<String> {
+        (!self.loser_uri_template.is_empty())
+            .then(|| fill_index(&self.loser_uri_template, first_ticket))
+    }
+
+    fn name_with_suffix(&self, suffix: String) -> String {
+        let mut prefix_len = MAX_NAME_LENGTH
+            .saturating_sub(suffix.len())
+            .min(self.name.len());
+        while !self.name.is_char_boundary(prefix_len) {
+            prefix_len -= 1;
+        }
+        format!("{}{}", &self.name[..prefix_len], suffix)
+    }
+}
+
+fn ticket_label(first_ticket: u64, quantity: u64) -> Result<String> {
+    require!(quantity > 0, ErrorCode::InvalidTicketQuantity);
+    if quantity == 1 {
+        return Ok(first_ticket.to_string());
+    }
+
+    let last_ticket = first_ticket
+        .checked_add(quantity - 1)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    Ok(format!("{}-{}", first_ticket, last_ticket))
+}
+
+fn fill_index(template: &str, first_ticket: u64) -> String {
+    template.replace(TICKET_INDEX_PLACEHOLDER, &first_ticket.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(name: &str, ticket_uri_template: &str) -> CollectionMetadata {
+        CollectionMetadata {
+            name: name.to_string(),
+            symbol: "TLT".to_string(),
+            uri: "https://example.com/collection.json".to_string(),
+            ticket_uri_template: ticket_uri_template.to_string(),
+            winner_uri_template: String::new(),
+            loser_uri_template: String::new(),
+        }
+    }
+
+    #[test]
+    fn ticket_names_keep_the_ticket_numbers() {
+        let short = metadata("Lottery", "");
+        assert_eq!(short.ticket_name(5, 1).unwrap(), "Lottery #5");
+        assert_eq!(short.ticket_name(5, 50).unwrap(), "Lottery #5-54");
+
+        let long = metadata("Ünïcödé Lottery With A Long Name", "");
+        let name = long.ticket_name(1_000_000, 1_000_000).unwrap();
+        assert!(name.len() <= MAX_NAME_LENGTH);
+        assert!(name.ends_with(" #1000000-1999999"));
+
+        assert_eq!(
+            short.winning_ticket_name(5, 50).unwrap(),
+            "Lottery #5-54 Winner"
+        );
+    }
+
+    #[test]
+    fn rejects_empty_or_overflowing_ranges() {
+        let metadata = metadata("Lottery", "");
+
+        assert!(metadata.ticket_name(5, 0).is_err());
+        assert!(metadata.ticket_name(u64::MAX, 2).is_err());
+        assert!(metadata.ticket_name(u64::MAX, 1).is_ok());
+    }
+
+    #[test]
+    fn ticket_uris_substitute_the_index() {
+        let metadata = metadata("Lottery", "https://example.com/{index}.json");
+
+        assert_eq!(metadata.ticket_uri(42), "https://example.com/42.json");
+        assert_eq!(metadata.winner_uri(42), None);
+        assert_eq!(metadata.loser_uri(42), None);
+
+        let marked = CollectionMetadata {
+            winner_uri_template: "https://example.com/winner/{index}.json".to_string(),
+            ..metadata
+        };
+        assert_eq!(
+            marked.winner_uri(42).as_deref(),
+            Some("https://example.com/winner/42.json")
+        );
+    }
+
+    #[test]
+    fn rejects_metadata_over_the_metaplex_limits() {
+        assert!(metadata("Lottery", "https://example.com/{index}.json")
+            .validate()
+            .is_ok());
+        assert!(metadata("", "").validate().is_err());
+        assert!(metadata(&"n".repeat(MAX_NAME_LENGTH + 1), "")
+            .validate()
+            .is_err());
+
+        // Fits as written, but not once the index is filled in
+        let template = format!(
+            "{}{}",
+            "u".repeat(MAX_URI_LENGTH - 7),
+            TICKET_INDEX_PLACEHOLDER
+        );
+        assert!(metadata("Lottery", &template).validate().is_err());
+    }
+}
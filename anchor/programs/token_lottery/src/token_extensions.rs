@@ -0,0 +1,80 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_spl::token_2022::spl_token_2022::{
+    extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+    state::Mint as MintState,
+};
+use anchor_spl::token_2022_extensions::{
+    spl_pod::optional_keys::OptionalNonZeroPubkey,
+    spl_token_metadata_interface::state::{Field, TokenMetadata},
+};
+
+use crate::ErrorCode;
+
+/// Size a Token-2022 mint reaches once its token metadata is written into it, on top of
+/// the fixed-size `extensions` (metadata and group pointers, group or member data).
+pub fn mint_len_with_metadata(
+    mint: &Pubkey,
+    update_authority: &Pubkey,
+    extensions: &[ExtensionType],
+    name: &str,
+    symbol: &str,
+    uri: &str,
+) -> Result<usize> {
+    let metadata = TokenMetadata {
+        update_authority: OptionalNonZeroPubkey::try_from(Some(*update_authority))?,
+        mint: *mint,
+        name: name.to_string(),
+        symbol: symbol.to_string(),
+        uri: uri.to_string(),
+        additional_metadata: Vec::new(),
+    };
+
+    let fixed_len = ExtensionType::try_calculate_account_len::<MintState>(extensions)?;
+    fixed_len
+        .checked_add(metadata.tlv_size_of()?)
+        .ok_or(ErrorCode::ArithmeticOverflow.into())
+}
+
+/// Size of a Token-2022 mint once `updates` are applied to its token metadata.
+pub fn mint_len_after_update(mint: &AccountInfo, updates: &[(Field, String)]) -> Result<usize> {
+    let mint_data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_data)?;
+    let mut metadata = mint_state.get_variable_len_extension::<TokenMetadata>()?;
+    let current_len = metadata.tlv_size_of()?;
+
+    for (field, value) in updates {
+        metadata.update(field.clone(), value.clone());
+    }
+
+    (mint_data.len() - current_len)
+        .checked_add(metadata.tlv_size_of()?)
+        .ok_or(ErrorCode::ArithmeticOverflow.into())
+}
+
+/// Tops the mint up to the rent-exempt minimum of `new_len`. Token-2022 grows the mint
+/// itself when metadata or group data is initialized, but doesn't fund the extra rent.
+pub fn fund_mint_growth<'info>(
+    mint: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    new_len: usize,
+) -> Result<()> {
+    let missing = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(mint.lamports());
+    if missing == 0 {
+        return Ok(());
+    }
+
+    system_program::transfer(
+        CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: payer.to_account_info(),
+                to: mint.clone(),
+            },
+        ),
+        missing,
+    )
+}
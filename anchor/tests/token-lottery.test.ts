@@ -14,6 +14,7 @@ describe('tokenlottery', () => {
 
   let switchboardProgram = new anchor.Program(SwitchboardIDL as anchor.Idl, provider)
   const rngKp = anchor.web3.Keypair.generate()
+  const lotteryId = new anchor.BN(Date.now())
 
   // fetch the switchboard idl and store it
   // beforeAll(async () => {
@@ -32,9 +33,9 @@ describe('tokenlottery', () => {
   //   switchboardProgram = new anchor.Program(switchboardIDL, provider);
   // });
 
-  async function buyTicket() {
+  async function buyTicket(quantity = 1) {
     const buyTicketIx = await program.methods
-      .buyTicket()
+      .buyTickets(lotteryId, new anchor.BN(quantity))
       .accounts({
         tokenProgram: TOKEN_PROGRAM_ID,
       })
@@ -63,9 +64,19 @@ describe('tokenlottery', () => {
     console.log('buy ticket ', sig)
   }
 
+  async function waitForSlot(slot: number) {
+    while ((await provider.connection.getSlot()) < slot) {
+      await new Promise((resolve) => setTimeout(resolve, 400))
+    }
+  }
+
   it('should test token lottery', async () => {
+    // Sales have to close before randomness can be committed
+    const endSlot = (await provider.connection.getSlot()) + 60
+    const queue = new anchor.web3.PublicKey('A43DyUGA7s8eXPxqEjJY6EBu1KKbNgfxF8h17VAHn13w')
+
     const initConfigIx = await program.methods
-      .initializeConfig(new anchor.BN(0), new anchor.BN(1863137100), new anchor.BN(10000))
+      .initializeConfig(lotteryId, new anchor.BN(0), new anchor.BN(endSlot), new anchor.BN(10000), [5000, 3000, 2000], new anchor.BN(3), { nft: {} }, new anchor.BN(1000), wallet.publicKey, 0, new anchor.BN(1500), { switchboard: { queue } })
       .instruction()
 
     const blockhashWithContext = await provider.connection.getLatestBlockhash()
@@ -80,7 +91,15 @@ describe('tokenlottery', () => {
     console.log('Your transaction signature', signature)
 
     const initLotteryIx = await program.methods
-      .initializeLottery()
+      .initializeLottery(lotteryId, {
+        name: 'Token Lottery',
+        symbol: 'TLT',
+        uri: 'https://raw.githubusercontent.com/solana-developers/developer-bootcamp-2024/refs/heads/main/project-9-token-lottery/metadata.json',
+        ticketUriTemplate:
+          'https://raw.githubusercontent.com/solana-developers/developer-bootcamp-2024/refs/heads/main/project-9-token-lottery/metadata.json',
+        winnerUriTemplate: '',
+        loserUriTemplate: '',
+      })
       .accounts({
         tokenProgram: TOKEN_PROGRAM_ID,
       })
@@ -107,9 +126,21 @@ describe('tokenlottery', () => {
     await buyTicket()
     await buyTicket()
     await buyTicket()
-    await buyTicket()
+    await buyTicket(5)
+
+    // Randomness can't be committed while tickets are still on sale; the sales check runs
+    // before the randomness account is read, so it doesn't need to exist yet
+    await expect(
+      program.methods
+        .commitRandomness(lotteryId)
+        .accounts({
+          randomnessAccount: rngKp.publicKey,
+        })
+        .rpc(),
+    ).rejects.toThrow(/LotteryNotCompleted/)
+
+    await waitForSlot(endSlot)
 
-    const queue = new anchor.web3.PublicKey('A43DyUGA7s8eXPxqEjJY6EBu1KKbNgfxF8h17VAHn13w')
     const queueAccount = new sb.Queue(switchboardProgram, queue)
 
     try {
@@ -137,7 +168,7 @@ describe('tokenlottery', () => {
     const sbCommitIx = await randomness.commitIx(queue)
 
     const commitIx = await program.methods
-      .commitRandomness()
+      .commitRandomness(lotteryId)
       .accounts({
         randomnessAccount: randomness.pubkey,
       })
@@ -168,5 +199,9 @@ describe('tokenlottery', () => {
     );
     
     console.log('commitSignature', commitSignature);
+
+    // The rejection is time-based: sales closed at end_time, which the commit had to wait
+    // for, so this fails with LotteryNotOpen rather than RandomnessAlreadyCommitted
+    await expect(buyTicket()).rejects.toThrow(/LotteryNotOpen/)
   }, 300000);
 })